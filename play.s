@@ -0,0 +1,32 @@
+.SacLabelInterned0:
+  .string "fact_l(20) = %lld\n"
+  .balign 4
+
+.SacLabelInterned1:
+  .string "fact_r(5) = %d\n\n"
+  .balign 4
+
+
+.global main
+main:
+  push {fp, lr}
+  mov fp, sp
+  sub sp, sp, #8
+  ldr r0, =.SacLabelInterned0
+  str r0, [sp, #0]
+  ldr r0, =20
+  bl fact_l
+  str r0, [sp, #4]
+  pop {r0, r1}
+  bl printf
+  sub sp, sp, #8
+  ldr r0, =.SacLabelInterned1
+  str r0, [sp, #0]
+  ldr r0, =5
+  bl fact_r
+  str r0, [sp, #4]
+  pop {r0, r1}
+  bl printf
+  mov sp, fp
+  mov r0, #0
+  pop {fp, pc}
@@ -0,0 +1,146 @@
+use std::{ffi::OsStr, path::Path};
+
+pub const USAGE: &str = "\
+usage: sac [options] <file.sac>...
+
+options:
+  -o <path>                   write output to <path> (only with a single input)
+  --emit=asm|ast|tokens       what to produce (default: asm)
+  --bounds-checks             trap on array indexes out of range
+  -h, --help                  show this message
+
+by default, `sac play.sac` writes ARM32 assembly to `play.s` in the current
+directory. `--emit=ast` and `--emit=tokens` print to stdout unless -o is given.";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Emit {
+    Asm,
+    Ast,
+    Tokens,
+}
+
+impl Emit {
+    fn from(name: &str) -> Option<Emit> {
+        match name {
+            "asm" => Some(Emit::Asm),
+            "ast" => Some(Emit::Ast),
+            "tokens" => Some(Emit::Tokens),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Options {
+    pub inputs: Vec<String>,
+    pub output: Option<String>,
+    pub emit: Emit,
+    pub bounds_checks: bool,
+    pub help: bool,
+}
+
+impl Options {
+    pub fn from_args<I>(args: I) -> Result<Options, String>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut opts = Options {
+            inputs: vec![],
+            output: None,
+            emit: Emit::Asm,
+            bounds_checks: false,
+            help: false,
+        };
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => opts.help = true,
+                "--bounds-checks" => opts.bounds_checks = true,
+                "-o" => match args.next() {
+                    Some(path) => opts.output = Some(path),
+                    None => return Err("`-o` expects an output path".into()),
+                },
+                _ if arg.starts_with("--emit=") => {
+                    let kind = &arg["--emit=".len()..];
+                    opts.emit = Emit::from(kind).ok_or(format!(
+                        "unknown emit kind `{kind}`, expected one of asm, ast, tokens"
+                    ))?;
+                }
+                _ if arg.starts_with('-') => return Err(format!("unknown option `{arg}`")),
+                _ => opts.inputs.push(arg),
+            }
+        }
+
+        if opts.help {
+            return Ok(opts);
+        }
+        if opts.inputs.is_empty() {
+            return Err("need sac file to compile...".into());
+        }
+        if opts.output.is_some() && opts.inputs.len() > 1 {
+            return Err(format!(
+                "`-o` can only be used with 1 sac file... got {}",
+                opts.inputs.len()
+            ));
+        }
+        Ok(opts)
+    }
+
+    // where the output for `input` should go. None means stdout
+    pub fn output_for(&self, input: &str) -> Option<String> {
+        if self.output.is_some() {
+            return self.output.clone();
+        }
+        match self.emit {
+            Emit::Asm => Some(
+                Path::new(input)
+                    .file_stem()
+                    .and_then(OsStr::to_str)
+                    .unwrap_or("out")
+                    .to_string()
+                    + ".s",
+            ),
+            Emit::Ast | Emit::Tokens => None,
+        }
+    }
+}
+
+#[cfg(test)]
+fn args(line: &str) -> Vec<String> {
+    line.split_whitespace().map(String::from).collect()
+}
+
+#[test]
+fn test_options_from_args() {
+    let opts = Options::from_args(args("play.sac")).unwrap();
+    assert_eq!(opts.inputs, vec!["play.sac".to_string()]);
+    assert_eq!(opts.emit, Emit::Asm);
+    assert_eq!(
+        opts.output_for("./examples/play.sac"),
+        Some("play.s".into())
+    );
+
+    let opts = Options::from_args(args("--emit=ast -o out.txt play.sac")).unwrap();
+    assert_eq!(opts.emit, Emit::Ast);
+    assert_eq!(opts.output_for("play.sac"), Some("out.txt".into()));
+
+    assert!(!opts.bounds_checks);
+    let opts = Options::from_args(args("--bounds-checks play.sac")).unwrap();
+    assert!(opts.bounds_checks);
+
+    let opts = Options::from_args(args("--emit=tokens a.sac b.sac")).unwrap();
+    assert_eq!(opts.inputs.len(), 2);
+    assert_eq!(opts.output_for("a.sac"), None);
+
+    assert!(Options::from_args(args("--help")).unwrap().help);
+}
+
+#[test]
+fn test_options_from_args_errors() {
+    assert!(Options::from_args(args("")).is_err());
+    assert!(Options::from_args(args("play.sac -o")).is_err());
+    assert!(Options::from_args(args("--emit=llvm play.sac")).is_err());
+    assert!(Options::from_args(args("--fast play.sac")).is_err());
+    assert!(Options::from_args(args("-o x.s a.sac b.sac")).is_err());
+}
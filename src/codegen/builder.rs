@@ -1,4 +1,14 @@
-use std::{collections::HashMap, ffi::OsStr, fs, path::Path};
+use std::{
+    collections::HashMap,
+    fs, io,
+    sync::atomic::{AtomicI32, Ordering},
+};
+
+use super::layout::Layout;
+use crate::frontend::{
+    ast::{NodeId, AST},
+    types::Type,
+};
 
 #[allow(dead_code)]
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -7,18 +17,15 @@ pub struct Label {
     value: i32,
 }
 
-static mut LABEL_COUNTER: i32 = 0;
+// atomic so files can be compiled on several threads at once
+static LABEL_COUNTER: AtomicI32 = AtomicI32::new(0);
 #[allow(dead_code)]
 impl Label {
     pub fn n() -> Self {
-        let label = Label {
-            value: unsafe { LABEL_COUNTER },
+        Label {
+            value: LABEL_COUNTER.fetch_add(1, Ordering::Relaxed),
             tag: String::new(),
-        };
-        unsafe {
-            LABEL_COUNTER += 1;
         }
-        label
     }
 
     pub fn nn(value: i32, tag: String) -> Self {
@@ -30,13 +37,26 @@ impl Label {
     }
 }
 
+// where a local lives in the frame: the offset of its lowest word, how many
+// words it takes, its type and, for a struct, which one it is
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Local {
+    pub offset: i32,
+    pub words: i32,
+    pub ty: Type,
+    pub layout: Option<String>,
+}
+
 #[allow(dead_code)]
 #[derive(Clone, Debug, PartialEq, Eq)]
 
 pub struct Context {
-    locals: HashMap<String, i32>,
+    locals: HashMap<String, Local>,
     next_local_offset: i32,
     outer: Option<Box<Context>>,
+    // a function's own scope. lookups stop here, the locals of an enclosing
+    // function live in a different frame
+    function: bool,
 }
 
 #[allow(dead_code)]
@@ -46,14 +66,24 @@ impl Context {
             locals: HashMap::new(),
             outer: None,
             next_local_offset: 0,
+            function: false,
         }
     }
 
+    // a block's locals go below the ones already in scope
     fn enter(&self) -> Self {
         Context {
             locals: HashMap::new(),
             outer: Some(Box::new(self.clone())),
-            next_local_offset: 0,
+            next_local_offset: self.next_local_offset,
+            function: false,
+        }
+    }
+
+    fn enter_function(&self) -> Self {
+        Context {
+            function: true,
+            ..self.enter()
         }
     }
 
@@ -61,12 +91,17 @@ impl Context {
         self.outer.clone()
     }
 
-    fn set(&mut self, local: String, offset: i32) {
-        self.locals.insert(local, offset);
+    fn set(&mut self, name: String, local: Local) {
+        self.locals.insert(name, local);
     }
 
-    fn get(&self, local: &String) -> Option<&i32> {
-        self.locals.get(local)
+    // the innermost declaration of `local`, so inner blocks shadow outer ones
+    fn get(&self, local: &String) -> Option<&Local> {
+        match (self.locals.get(local), &self.outer) {
+            (Some(offset), _) => Some(offset),
+            (None, Some(outer)) if !self.function => outer.get(local),
+            _ => None,
+        }
     }
 }
 
@@ -75,6 +110,28 @@ pub struct Builder {
     lines: Vec<String>,
     context: Context,
     interned_strings: Vec<(String, Label)>,
+    // runtime error routines used so far, with what they report
+    traps: Vec<(Label, String)>,
+    // where `continue` and `break` jump to in the loops being generated,
+    // innermost last
+    loops: Vec<(Label, Label)>,
+    // whether array indexes are checked at runtime
+    bounds_checks: bool,
+    // for each function being generated, innermost last: the line that
+    // reserves its locals, the bytes of parameters pushed before it and the
+    // lowest offset a local has used
+    frames: Vec<(usize, i32, i32)>,
+    // top level `global`s and their initial values, and folded `const`s
+    globals: Vec<(String, i32)>,
+    consts: HashMap<String, i32>,
+    // the layouts of the structs the file declares, by name
+    structs: HashMap<String, Layout>,
+    // the types the type checker found, by the node that has them
+    types: HashMap<NodeId, Type>,
+    // the parameter and return types of the file's functions
+    functions: HashMap<String, (Vec<Type>, Type)>,
+    // what the functions being generated return, innermost last
+    returns: Vec<Type>,
 }
 
 impl Builder {
@@ -84,9 +141,27 @@ impl Builder {
             lines: vec![],
             context: Context::n(),
             interned_strings: vec![],
+            traps: vec![],
+            loops: vec![],
+            frames: vec![],
+            bounds_checks: false,
+            globals: vec![],
+            consts: HashMap::new(),
+            structs: HashMap::new(),
+            types: HashMap::new(),
+            functions: HashMap::new(),
+            returns: vec![],
         }
     }
 
+    pub fn check_bounds(&mut self, on: bool) {
+        self.bounds_checks = on;
+    }
+
+    pub fn checks_bounds(&self) -> bool {
+        self.bounds_checks
+    }
+
     pub fn add(&mut self, line: &str) {
         self.lines.push(line.into());
     }
@@ -95,61 +170,165 @@ impl Builder {
         self.lines.insert(loc, line.into());
     }
 
-    pub fn write_out(&mut self) {
-        if !self.interned_strings.is_empty() {
-            self.add_at("", 0);
-            for (istr, label) in self.interned_strings.clone().iter().rev() {
-                // generates:
-                // label:
-                //   .string "string contents"
-                //   .balign
-                self.add_at(
-                    &format!(
-                        r#"
+    // the finished assembly, interned strings first
+    pub fn asm(&self) -> String {
+        let mut lines = vec![];
+        for (istr, label) in &self.interned_strings {
+            // generates:
+            // label:
+            //   .string "string contents"
+            //   .balign
+            lines.push(format!(
+                r#"
 {}:
   .string "{}"
   .balign 4"#,
-                        label.s(),
-                        istr,
-                    ),
-                    0,
-                );
+                label.s(),
+                istr,
+            ));
+        }
+        // initialized globals go in .data, zeroed ones in .bss
+        for (section, zeroed) in [(".data", false), (".bss", true)] {
+            let globals: Vec<_> = self
+                .globals
+                .iter()
+                .filter(|(_, value)| (*value == 0) == zeroed)
+                .collect();
+            if globals.is_empty() {
+                continue;
             }
+            lines.push(format!("  {section}"));
+            lines.push("  .balign 4".into());
+            for (name, value) in globals {
+                lines.push(format!("{}:", Builder::global_label(name).s()));
+                lines.push(match zeroed {
+                    true => "  .space 4".into(),
+                    false => format!("  .word {value}"),
+                });
+            }
+        }
+        if !self.globals.is_empty() {
+            lines.push("  .text".into());
+        }
+        if !lines.is_empty() {
+            lines.push(String::new());
         }
-        let mut asm = self.lines.join("\n").trim_start().to_string();
+        lines.extend(self.lines.iter().cloned());
+        for (label, msg) in &self.traps {
+            let msg = format!("sac: {msg}\\n");
+            lines.push(String::new());
+            lines.push(format!("{}:", label.s()));
+            lines.push("  mov r0, #2".into());
+            lines.push(format!("  ldr r1, ={}", label.s() + "Msg"));
+            // the message length, counting the escaped newline as one byte
+            lines.push(format!("  mov r2, #{}", msg.len() - 1));
+            lines.push("  bl write".into());
+            lines.push("  mov r0, #1".into());
+            lines.push("  bl exit".into());
+            lines.push(format!("{}Msg:", label.s()));
+            lines.push(format!("  .string \"{msg}\""));
+            lines.push("  .balign 4".into());
+        }
+        let mut asm = lines.join("\n").trim_start().to_string();
         asm += "\n";
-        let output = Path::new(&self.src)
-            .file_stem()
-            .and_then(OsStr::to_str)
-            .unwrap()
-            .to_string()
-            + ".s";
-        fs::write(output, asm).unwrap();
-    }
-
-    pub fn get_src(&self) -> String {
-        fs::read_to_string(&self.src).unwrap()
-    }
-
-    pub fn set_up_env(&mut self, locals: &[String]) {
-        let max_span = locals.len() * 4;
-        for (i, local) in locals.iter().enumerate() {
-            self.context.set(local.clone(), (4 * i - max_span) as i32);
-            // for 1 local,
-            //  - max_span = 4
-            //  - arg 0, offset = 4 * 0 - 4 = -4
-            // for 3 locals,
-            //  - max_span = 12
-            //  - arg 0, offset = 4 * 0 - 12 = -12
-            //  - arg 1, offset = 4 * 1 - 12 = -8
-            //  - arg 2, offset = 4 * 2 - 12 = -4
-        }
-        // go one past the last argument
-        // with 1 local,
-        // next_local_offset = (1 * -4) - 4 = -8 (since the only local will be in -4)
-        // with 3 locals,
-        // next_local_offset = (3 * -4) - 4 = -12 - 4 = -16 (since the first local will be at -12)
-        self.context.next_local_offset = (locals.len() as i32 * -4) - 4;
+        asm
+    }
+
+    pub fn write_out(&self, output: &str) -> io::Result<()> {
+        fs::write(output, self.asm())
+    }
+
+    pub fn write_to<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_all(self.asm().as_bytes())
+    }
+
+    pub fn get_src(&self) -> io::Result<String> {
+        fs::read_to_string(&self.src)
+    }
+
+    pub fn set_types(&mut self, types: HashMap<NodeId, Type>) {
+        self.types = types;
+    }
+
+    // the type of `node`. anything not checked is a word
+    pub fn type_of(&self, node: &AST) -> Type {
+        self.types.get(&node.id()).cloned().unwrap_or(Type::Any)
+    }
+
+    pub fn add_function(&mut self, name: String, params: Vec<Type>, ret: Type) {
+        self.functions.insert(name, (params, ret));
+    }
+
+    // the parameter types of sac function `name`
+    pub fn try_get_params(&self, name: &str) -> Option<&[Type]> {
+        self.functions
+            .get(name)
+            .map(|(params, _)| params.as_slice())
+    }
+
+    // what the function being generated returns
+    pub fn returns(&self) -> Type {
+        self.returns.last().cloned().unwrap_or(Type::Any)
+    }
+
+    pub fn set_up_env(&mut self, params: &[String], types: &[Type]) {
+        // the parameters that came in registers were pushed just below fp,
+        // the caller left the rest above the fp and lr the prologue saved:
+        //  - with 1 param, param 0 is at -4
+        //  - with 3 params, params 0, 1 and 2 are at -12, -8 and -4
+        //  - with 6 params, params 4 and 5 are at 8 and 12
+        let (slots, in_regs, _) = arg_slots(types);
+        let max_span = in_regs as i32 * 4;
+        for (i, param) in params.iter().enumerate() {
+            let ty = types.get(i).cloned().unwrap_or(Type::Any);
+            let offset = match slots.get(i) {
+                Some(&slot) if slot < 16 => slot - max_span,
+                Some(&slot) => 8 + slot - 16,
+                None => 0,
+            };
+            let local = Local {
+                offset,
+                words: ty.words(),
+                ty,
+                layout: None,
+            };
+            self.context.set(param.clone(), local);
+        }
+        // go one past the last parameter pushed
+        // with 1 param,
+        // next_local_offset = (1 * -4) - 4 = -8 (since the only param will be in -4)
+        // with 3 params,
+        // next_local_offset = (3 * -4) - 4 = -12 - 4 = -16 (since the first param will be at -12)
+        self.context.next_local_offset = -max_span - 4;
+    }
+
+    // starts the body of a function whose parameters have been pushed. its
+    // locals get fixed slots in a frame reserved here, so a block's slots can
+    // be reused once it ends and a loop body does not grow the stack
+    pub fn enter_frame(&mut self, params: &[String], types: &[Type], ret: Type) {
+        self.context = self.context.enter_function();
+        self.set_up_env(params, types);
+        self.returns.push(ret);
+        let pushed = arg_slots(types).1 as i32 * 4;
+        self.frames.push((self.lines.len(), pushed, -pushed));
+        // filled in by exit_frame, when the size of the frame is known
+        self.add("");
+    }
+
+    pub fn exit_frame(&mut self) -> Result<(), String> {
+        let (line, pushed, lowest) = self.frames.pop().ok_or("cannot leave orphaned frame")?;
+        self.returns.pop();
+        self.exit_ctx()?;
+        // sp stays 8-byte aligned for calls
+        let size = (-lowest + 7) / 8 * 8 - pushed;
+        if size > 0 && encodable(size) {
+            self.lines[line] = format!("  sub sp, sp, #{size}");
+        } else if size > 0 {
+            self.lines[line] = format!("  ldr ip, ={size}\n  sub sp, sp, ip");
+        } else {
+            self.lines.remove(line);
+        }
+        Ok(())
     }
 
     pub fn enter_ctx(&mut self) {
@@ -157,23 +336,111 @@ impl Builder {
         self.context = new_ctx;
     }
 
-    pub fn exit_ctx(&mut self) {
-        if let Some(ctx) = self.context.exit() {
-            self.context = *ctx;
-        } else {
-            panic!("cannot leave orphaned scope :(");
+    pub fn exit_ctx(&mut self) -> Result<(), String> {
+        match self.context.exit() {
+            Some(ctx) => {
+                self.context = *ctx;
+                Ok(())
+            }
+            None => Err("cannot leave orphaned scope".into()),
         }
     }
 
-    pub fn set(&mut self, local: String) {
-        self.context.set(local, self.context.next_local_offset);
-        self.context.next_local_offset -= 4;
+    pub fn enter_loop(&mut self, next: Label, end: Label) {
+        self.loops.push((next, end));
+    }
+
+    pub fn exit_loop(&mut self) {
+        self.loops.pop();
+    }
+
+    // where `continue` and `break` jump to
+    pub fn current_loop(&self) -> Option<(Label, Label)> {
+        self.loops.last().cloned()
+    }
+
+    // gives `local` the free words of the frame a `ty` needs and returns the
+    // offset of the lowest, where an array's element 0 is
+    pub fn set(&mut self, local: String, ty: Type) -> i32 {
+        self.alloc(local, ty.words(), ty, None)
+    }
+
+    // like `set`, for an instance of struct `layout`
+    pub fn set_struct(&mut self, local: String, layout: &str) -> Option<i32> {
+        let words = self.structs.get(layout)?.words();
+        let ty = Type::Struct(layout.into());
+        Some(self.alloc(local, words, ty, Some(layout.into())))
+    }
+
+    fn alloc(&mut self, name: String, words: i32, ty: Type, layout: Option<String>) -> i32 {
+        let offset = self.context.next_local_offset - 4 * (words - 1);
+        let local = Local {
+            offset,
+            words,
+            ty,
+            layout,
+        };
+        self.context.set(name, local);
+        self.context.next_local_offset -= 4 * words;
+        if let Some((_, _, lowest)) = self.frames.last_mut() {
+            *lowest = (*lowest).min(offset);
+        }
+        offset
     }
 
-    pub fn try_get(&self, local: &String) -> Option<&i32> {
+    pub fn try_get(&self, local: &String) -> Option<&Local> {
         self.context.get(local)
     }
 
+    fn global_label(name: &str) -> Label {
+        Label::nn(0, format!("Global{name}"))
+    }
+
+    pub fn add_global(&mut self, name: String, value: i32) {
+        self.globals.push((name, value));
+    }
+
+    // the label of global `name`'s word
+    pub fn try_get_global(&self, name: &str) -> Option<Label> {
+        self.globals
+            .iter()
+            .find(|(global, _)| global == name)
+            .map(|(name, _)| Builder::global_label(name))
+    }
+
+    pub fn add_struct(&mut self, name: String, layout: Layout) {
+        self.structs.insert(name, layout);
+    }
+
+    pub fn try_get_struct(&self, name: &str) -> Option<&Layout> {
+        self.structs.get(name)
+    }
+
+    pub fn add_const(&mut self, name: String, value: i32) {
+        self.consts.insert(name, value);
+    }
+
+    pub fn try_get_const(&self, name: &str) -> Option<i32> {
+        self.consts.get(name).copied()
+    }
+
+    // the constants not shadowed by a local in view
+    pub fn consts(&self) -> HashMap<String, i32> {
+        let mut consts = self.consts.clone();
+        consts.retain(|name, _| self.context.get(name).is_none());
+        consts
+    }
+
+    // a routine that prints `msg` to stderr and exits with status 1, for
+    // errors only caught at runtime. code branches to the returned label
+    pub fn trap(&mut self, tag: &str, msg: &str) -> Label {
+        let label = Label::nn(0, format!("Trap{tag}"));
+        if !self.traps.iter().any(|(trap, _)| trap == &label) {
+            self.traps.push((label.clone(), msg.into()));
+        }
+        label
+    }
+
     pub fn add_interned_str(&mut self, istr: String) -> Label {
         for (e_istr, ilabel) in &self.interned_strings {
             if e_istr == &istr {
@@ -186,3 +453,40 @@ impl Builder {
         label
     }
 }
+
+// where each argument of a call goes, as bytes: 0-15 are r0-r3 and 16 on the
+// stack above them. an i64 takes an even and odd pair of registers, or 8
+// aligned bytes of stack, and once one has gone to the stack so do all the
+// arguments after it. also gives the registers used and the bytes of stack,
+// padded so sp stays 8-byte aligned
+pub fn arg_slots(types: &[Type]) -> (Vec<i32>, usize, i32) {
+    let (mut reg, mut stack) = (0, 0);
+    let mut slots = vec![];
+    for ty in types {
+        if ty == &Type::I64 {
+            reg += reg % 2;
+            if reg + 2 <= 4 {
+                slots.push(reg * 4);
+                reg += 2;
+            } else {
+                reg = 4;
+                stack += stack % 8;
+                slots.push(16 + stack);
+                stack += 8;
+            }
+        } else if reg < 4 {
+            slots.push(reg * 4);
+            reg += 1;
+        } else {
+            slots.push(16 + stack);
+            stack += 4;
+        }
+    }
+    (slots, reg as usize, (stack + 7) / 8 * 8)
+}
+
+// whether `value` fits in an instruction: 8 bits rotated right by an even
+// amount
+fn encodable(value: i32) -> bool {
+    (0..16).any(|rot| (value as u32).rotate_left(2 * rot) <= 0xff)
+}
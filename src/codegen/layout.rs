@@ -0,0 +1,27 @@
+// where the fields of a struct live. every field is one word, in the order
+// they are declared, from the struct's lowest address up
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Layout {
+    fields: Vec<String>,
+}
+
+#[allow(dead_code)]
+impl Layout {
+    pub fn n(fields: &[String]) -> Self {
+        Layout {
+            fields: fields.to_vec(),
+        }
+    }
+
+    pub fn words(&self) -> i32 {
+        self.fields.len() as i32
+    }
+
+    // bytes from the start of the struct to `field`
+    pub fn offset(&self, field: &str) -> Option<i32> {
+        self.fields
+            .iter()
+            .position(|name| name == field)
+            .map(|i| 4 * i as i32)
+    }
+}
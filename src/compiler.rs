@@ -0,0 +1,627 @@
+use std::{fmt, io};
+
+use crate::codegen::builder::Builder;
+use crate::frontend::{
+    ast::AST,
+    checker,
+    diagnostics::Diagnostic,
+    sac_parser::{error_node, sac_parser},
+    source::Source,
+    typeck,
+};
+
+/// Everything that can stop a sac file from compiling. None of it aborts the
+/// process, so the compiler can be used from other tools.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CompileError {
+    // reading the source or writing the output failed
+    Io { path: String, msg: String },
+    // the file is not valid sac syntax
+    Parse(Diagnostic),
+    // the file parses but names, arity or declarations are wrong
+    Check(Diagnostic),
+    // the checked tree uses something codegen cannot do yet
+    Codegen(Diagnostic),
+}
+
+impl CompileError {
+    pub fn diagnostic(&self) -> Option<&Diagnostic> {
+        match self {
+            CompileError::Io { .. } => None,
+            CompileError::Parse(diag) | CompileError::Check(diag) | CompileError::Codegen(diag) => {
+                Some(diag)
+            }
+        }
+    }
+
+    /// The error in rustc style. `src` is the contents of the file it is about.
+    pub fn render(&self, src: &str) -> String {
+        match self.diagnostic() {
+            Some(diag) => diag.render(src),
+            None => format!("error: {self}\n"),
+        }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::Io { path, msg } => write!(f, "cannot access `{path}`: {msg}"),
+            CompileError::Parse(diag) | CompileError::Check(diag) | CompileError::Codegen(diag) => {
+                write!(f, "{}: {}", diag.span, diag.msg)
+            }
+        }
+    }
+}
+
+/// How code is generated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Settings {
+    /// Check array indexes at runtime. An index out of range prints
+    /// `sac: array index out of bounds` and exits with status 1.
+    pub bounds_checks: bool,
+}
+
+/// Parses a whole file. Every syntax error in it is reported, not just the
+/// first one.
+pub fn parse(source: Source<'_>) -> Result<AST, Vec<CompileError>> {
+    let ast = match sac_parser(source) {
+        Ok((_, ast, _)) => ast,
+        Err(err) => error_node(&err),
+    };
+    let errors = ast.errors();
+    if errors.is_empty() {
+        Ok(ast)
+    } else {
+        Err(errors.into_iter().map(CompileError::Parse).collect())
+    }
+}
+
+/// Resolves the names in a parsed file: undefined variables and functions,
+/// calls with the wrong number of arguments, duplicate definitions and uses
+/// before declaration. Once they all resolve, checks that the types fit.
+pub fn check(ast: &AST) -> Result<(), Vec<CompileError>> {
+    let mut diagnostics = checker::check(ast);
+    // types are only checked once every name resolves
+    if diagnostics.is_empty() {
+        diagnostics = typeck::check(ast);
+    }
+    if diagnostics.is_empty() {
+        Ok(())
+    } else {
+        Err(diagnostics.into_iter().map(CompileError::Check).collect())
+    }
+}
+
+// generates code for a checked file
+fn build(ast: &AST, settings: Settings) -> Result<Builder, Vec<CompileError>> {
+    let mut b = Builder::n(ast.get_span().file);
+    b.check_bounds(settings.bounds_checks);
+    b.set_types(typeck::types(ast));
+    ast.declare_globals(&mut b)
+        .map_err(|diag| vec![CompileError::Codegen(diag)])?;
+    ast.emit_arm32(&mut b)
+        .map_err(|diag| vec![CompileError::Codegen(diag)])?;
+    Ok(b)
+}
+
+/// Generates ARM32 assembly for a file that passed [`check`].
+pub fn emit(ast: &AST) -> Result<String, Vec<CompileError>> {
+    emit_with(ast, Settings::default())
+}
+
+/// Like [`emit`], with `settings` instead of the defaults.
+pub fn emit_with(ast: &AST, settings: Settings) -> Result<String, Vec<CompileError>> {
+    Ok(build(ast, settings)?.asm())
+}
+
+/// Like [`emit`], but writes the assembly to `out`.
+pub fn emit_to<W: io::Write>(ast: &AST, out: &mut W) -> Result<(), Vec<CompileError>> {
+    build(ast, Settings::default())?
+        .write_to(out)
+        .map_err(|err| {
+            vec![CompileError::Io {
+                path: ast.get_span().file,
+                msg: err.to_string(),
+            }]
+        })
+}
+
+/// Parses, checks and generates ARM32 assembly for a file.
+pub fn compile(source: Source<'_>) -> Result<String, Vec<CompileError>> {
+    compile_with(source, Settings::default())
+}
+
+/// Like [`compile`], with `settings` instead of the defaults.
+pub fn compile_with(source: Source<'_>, settings: Settings) -> Result<String, Vec<CompileError>> {
+    let ast = parse(source)?;
+    check(&ast)?;
+    emit_with(&ast, settings)
+}
+
+/// Like [`compile`], but writes the assembly to `out`.
+pub fn compile_to<W: io::Write>(source: Source<'_>, out: &mut W) -> Result<(), Vec<CompileError>> {
+    let ast = parse(source)?;
+    check(&ast)?;
+    emit_to(&ast, out)
+}
+
+#[test]
+fn test_compile() {
+    let src = ":main {\n  printf(\"%d\\n\", twice(21));\n}\n:twice n { ret n * 2; }\n";
+    let asm = compile(Source::new("ok.sac", src)).unwrap();
+    assert!(asm.starts_with(".SacLabelInterned0:\n  .string \"%d\\n\""));
+    assert!(asm.contains(".global main\nmain:"));
+    assert!(asm.contains("  bl twice"));
+
+    let mut out = vec![];
+    compile_to(Source::new("ok.sac", src), &mut out).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap().lines().count(),
+        asm.lines().count()
+    );
+}
+
+#[test]
+fn test_compile_relational() {
+    let src = ":main {\n  mut i = 0;\n  while i < 10 { i = i + 1; }\n  ret i >= 10;\n}\n";
+    let asm = compile(Source::new("loop.sac", src)).unwrap();
+    // comparisons are signed, with the lhs in r1
+    assert!(asm.contains("  cmp r1, r0\n  movlt r0, #1\n  movge r0, #0\n"));
+    assert!(asm.contains("  cmp r1, r0\n  movge r0, #1\n  movlt r0, #0\n"));
+}
+
+#[test]
+fn test_compile_short_circuit() {
+    let src = ":main {\n  ret false && f();\n}\n:f { ret 1; }\n";
+    let asm = compile(Source::new("and.sac", src)).unwrap();
+    // a false lhs jumps over the call
+    let skip = asm.find("  beq .SacLabel").unwrap();
+    assert!(skip < asm.find("  bl f").unwrap());
+}
+
+#[test]
+fn test_compile_division() {
+    let src = ":main {\n  ret -7 / 2 + -7 % 2;\n}\n";
+    let asm = compile(Source::new("div.sac", src)).unwrap();
+    assert!(asm.contains("  beq .SacLabelTrapDivByZero0\n  sdiv r0, r1, r0\n"));
+    assert!(asm.contains("  sdiv r2, r1, r0\n  mls r0, r2, r0, r1\n"));
+    // the trap is emitted once, however many divisions use it
+    assert_eq!(asm.matches(".SacLabelTrapDivByZero0:").count(), 1);
+    assert!(asm.contains("  .string \"sac: division by zero\\n\""));
+}
+
+#[test]
+fn test_compile_bitwise() {
+    let src = ":main {\n  ret (~1 & 2 | 3 ^ 4) << 1 >> 2;\n}\n";
+    let asm = compile(Source::new("bits.sac", src)).unwrap();
+    for instr in [
+        "mvn r0, r0",
+        "and r0, r1, r0",
+        "orr r0, r1, r0",
+        "eor r0, r1, r0",
+    ] {
+        assert!(asm.contains(instr), "missing `{instr}`");
+    }
+    assert!(asm.contains("lsl r0, r1, r0"));
+    assert!(asm.contains("asr r0, r1, r0"));
+}
+
+#[test]
+fn test_compile_stack_arguments() {
+    let src = "\
+:main {
+  printf(\"%d %d %d %d %d\\n\", 1, 2, 3, 4, sum(1, 2, 3, 4, 5, 6));
+}
+:sum a, b, c, d, e, f { ret a + e + f; }
+";
+    let asm = compile(Source::new("args.sac", src)).unwrap();
+    // printf: 4 in registers, 2 on the stack
+    assert!(asm.contains("  sub sp, sp, #24\n"));
+    assert!(asm
+        .contains("  str r0, [sp, #20]\n  pop {r0, r1, r2, r3}\n  bl printf\n  add sp, sp, #8\n"));
+    // sum: 4 in registers, 2 on the stack and 8 bytes of it
+    assert!(asm.contains("  bl sum\n  add sp, sp, #8\n"));
+    // sum reads e and f above its saved fp and lr
+    assert!(asm.contains("  push {r0, r1, r2, r3}\n"));
+    assert!(asm.contains("  ldr r0, [fp, #8]\n"));
+    assert!(asm.contains("  ldr r0, [fp, #12]\n"));
+    assert!(asm.contains("  ldr r0, [fp, #-16]\n"));
+
+    // an odd number of stack arguments is padded to keep sp 8-byte aligned
+    let src = ":main {\n  f(1, 2, 3, 4, 5);\n}\n:f a, b, c, d, e { ret e; }\n";
+    let asm = compile(Source::new("args.sac", src)).unwrap();
+    assert!(asm.contains("  sub sp, sp, #24\n"));
+    assert!(asm.contains("  bl f\n  add sp, sp, #8\n"));
+
+    // three register arguments take 16 bytes, so the call to g made while
+    // they are being written finds sp 8-byte aligned
+    let src = "\
+:f a, b, c { ret a + b + c; }
+:g x { ret x; }
+:main { mut x = 1; f(x, 2, g(x)); }
+";
+    let asm = compile(Source::new("args.sac", src)).unwrap();
+    assert!(asm.contains("  sub sp, sp, #16\n"));
+    assert!(asm.contains("  bl g\n  str r0, [sp, #8]\n  pop {r0, r1, r2, r3}\n  bl f\n"));
+}
+
+#[test]
+fn test_compile_if_without_else() {
+    let src = ":main {\n  if true { printf(\"one\"); }\n  ret 0;\n}\n";
+    let asm = compile(Source::new("if.sac", src)).unwrap();
+    // a false condition jumps past the then, there is no else to skip
+    let (_, after) = asm.split_once("  beq ").unwrap();
+    let (label, after) = after.split_once('\n').unwrap();
+    assert!(after.contains(&format!("  bl printf\n{label}:\n  ldr r0, =0\n")));
+    assert!(!asm.contains("  b .SacLabel"));
+}
+
+#[test]
+fn test_compile_break_continue() {
+    let src = ":main {\n  while true {\n    if false { continue; }\n    break;\n  }\n}\n";
+    let asm = compile(Source::new("loop.sac", src)).unwrap();
+    // labels are numbered globally, so find the loop's from its own code
+    let start = asm
+        .lines()
+        .find(|line| line.ends_with(':') && line.starts_with(".SacLabel"))
+        .unwrap();
+    let start = start.trim_end_matches(':');
+    let end = asm.split_once("  beq ").unwrap().1.lines().next().unwrap();
+    assert!(asm.contains(&format!("  b {start}\n")));
+    assert!(asm.contains(&format!("  b {end}\n")));
+    // continue, then the jump back at the end of the body
+    assert_eq!(asm.matches(&format!("  b {start}\n")).count(), 2);
+}
+
+#[test]
+fn test_compile_for() {
+    let src = ":main {\n  for i in 0..10 {\n    if i % 2 != 0 { continue; }\n    printf(\"%d\\n\", i);\n  }\n}\n";
+    let asm = compile(Source::new("for.sac", src)).unwrap();
+    // `continue` jumps to the increment, which sits between the body and
+    // the jump back to the condition
+    let (_, step) = asm.split_once("  bl printf\n").unwrap();
+    let step_label = step.lines().next().unwrap().trim_end_matches(':');
+    assert!(asm.contains(&format!("  b {step_label}\n")));
+    assert!(step.contains("  add r0, r0, r1\n  str r0, [fp, #-4]\n  b .SacLabel"));
+}
+
+#[test]
+fn test_compile_errors() {
+    let errors = compile(Source::new(
+        "bad.sac",
+        ":main {\n  mut x = ;\n  ret 1 +;\n}",
+    ))
+    .unwrap_err();
+    assert_eq!(errors.len(), 2);
+    assert!(errors
+        .iter()
+        .all(|err| matches!(err, CompileError::Parse(_))));
+
+    let errors = compile(Source::new("bad.sac", ":main {\n  ret f(x);\n}")).unwrap_err();
+    assert_eq!(
+        errors.iter().map(|err| err.to_string()).collect::<Vec<_>>(),
+        vec![
+            "bad.sac:2:9: undefined variable `x`",
+            "bad.sac:2:7: cannot find function `f`",
+        ]
+    );
+
+    // the broken statement is reported before the `}` it left missing
+    let errors = compile(Source::new("bad.sac", ":main {\n  ret 1 + foo(;")).unwrap_err();
+    assert_eq!(
+        errors.iter().map(|err| err.to_string()).collect::<Vec<_>>(),
+        vec![
+            "bad.sac:2:15: expected expression or `)`, found `;`",
+            "bad.sac:2:16: expected statement or `}`, found end of input",
+        ]
+    );
+}
+
+#[test]
+fn test_compile_block_scopes() {
+    let src = "\
+:main {
+  mut x = 1;
+  if x > 0 { mut y = 2; mut x = y; }
+  while x < 10 { mut t = x; x = t + 1; }
+  ret x;
+}
+";
+    let asm = compile(Source::new("scopes.sac", src)).unwrap();
+    // locals live in a frame reserved up front, not pushed one at a time
+    assert!(asm.contains("  mov fp, sp\n  sub sp, sp, #16\n"));
+    assert!(!asm.contains("push {r0}"));
+    // the inner x shadows the outer one in a slot of its own
+    assert!(asm.contains("  ldr r0, [fp, #-8]\n  str r0, [fp, #-12]\n"));
+    // t reuses y's slot once the if has ended, and the loop reads the outer x
+    assert!(asm.contains("  ldr r0, [fp, #-4]\n  str r0, [fp, #-8]\n"));
+    assert!(asm.contains("  ldr r0, [fp, #-4]\n  mov sp, fp\n"));
+}
+
+#[test]
+fn test_compile_globals() {
+    let src = "\
+const N = 4;
+global total = 0;
+global step = N / 2;
+:main {
+  for i in 0..N { total = total + step; }
+  ret total;
+}
+";
+    let asm = compile(Source::new("globals.sac", src)).unwrap();
+    assert!(asm.starts_with(".data\n  .balign 4\n.SacLabelGlobalstep0:\n  .word 2\n"));
+    assert!(asm.contains("  .bss\n  .balign 4\n.SacLabelGlobaltotal0:\n  .space 4\n  .text\n"));
+    // globals are read and written through their address, constants are
+    // folded into the code
+    assert!(asm.contains("  ldr r1, =.SacLabelGlobaltotal0\n  ldr r0, [r1]\n"));
+    assert!(asm.contains("  ldr r1, =.SacLabelGlobaltotal0\n  str r0, [r1]\n"));
+    assert!(asm.contains("  ldr r0, =4\n"));
+    assert!(!asm.contains("N"));
+}
+
+#[test]
+fn test_compile_arrays() {
+    let src = "\
+:main {
+  mut buf = [7; 16];
+  for i in 0..16 { buf[i] = buf[i] * i; }
+  ret buf[3];
+}
+";
+    let asm = compile(Source::new("arrays.sac", src)).unwrap();
+    // 16 words and the loop variables, 8-byte aligned
+    assert!(asm.contains("  mov fp, sp\n  sub sp, sp, #72\n"));
+    // every element is filled with the value, from element 0 up
+    assert!(asm.contains("  ldr r1, =-64\n  add r1, fp, r1\n  ldr r2, =16\n"));
+    assert!(asm.contains("  str r0, [r1], #4\n  subs r2, r2, #1\n  bne .SacLabel"));
+    assert!(asm.contains("  ldr r1, =-64\n  add r1, fp, r1\n  ldr r0, [r1, r0, lsl #2]\n"));
+    assert!(asm.contains("  ldr r2, =-64\n  add r2, fp, r2\n  str r0, [r2, r1, lsl #2]\n"));
+    assert!(!asm.contains("OutOfBounds"));
+
+    let settings = Settings {
+        bounds_checks: true,
+    };
+    let asm = compile_with(Source::new("arrays.sac", src), settings).unwrap();
+    assert!(asm.contains("  ldr r2, =16\n  cmp r0, r2\n  bhs .SacLabelTrapOutOfBounds0\n"));
+    assert!(asm.contains("  ldr r2, =16\n  cmp r1, r2\n  bhs .SacLabelTrapOutOfBounds0\n"));
+    assert!(asm.contains("  .string \"sac: array index out of bounds\\n\""));
+
+    // frames too big for an immediate are reserved through a register
+    let src = ":main {\n  mut big = [0; 1001];\n  ret big[1000];\n}\n";
+    let asm = compile(Source::new("big.sac", src)).unwrap();
+    assert!(asm.contains("  ldr ip, =4008\n  sub sp, sp, ip\n"));
+}
+
+#[test]
+fn test_compile_pointers() {
+    let src = "\
+global total = 0;
+:main {
+  mut x = 1;
+  mut buf = [0; 4];
+  mut p = &x;
+  *p = *p * 2;
+  scanf(\"%d\", &buf[1]);
+  add(&total, x);
+  ret total;
+}
+:add to, n { *to = *to + n; }
+";
+    let asm = compile(Source::new("ptr.sac", src)).unwrap();
+    // addresses are fp plus the local's offset, or a global's label
+    assert!(asm.contains("  ldr r0, =-4\n  add r0, fp, r0\n  str r0, [fp, #-24]\n"));
+    assert!(asm.contains("  ldr r1, =-20\n  add r1, fp, r1\n  add r0, r1, r0, lsl #2\n"));
+    assert!(asm.contains("  ldr r0, =.SacLabelGlobaltotal0\n"));
+    // `*p = v` keeps the address in r2, r1 may hold the value's high word
+    assert!(asm.contains("  ldr r0, [fp, #-24]\n  ldr r0, [r0]\n"));
+    assert!(asm.contains("  pop {r2, ip}\n  str r0, [r2]\n"));
+}
+
+#[test]
+fn test_compile_structs() {
+    let src = "\
+struct Point { x, y }
+:main {
+  mut n = 3;
+  mut p = Point { y: n, x: 1 };
+  for i in 0..n { p.x = p.x + p.y; }
+  scanf(\"%d\", &p.y);
+  ret p.x;
+}
+";
+    let asm = compile(Source::new("structs.sac", src)).unwrap();
+    // p takes two words, x at the lower address
+    assert!(asm.contains("  mov fp, sp\n  sub sp, sp, #24\n"));
+    // fields are generated in the order they are written, then stored
+    assert!(asm.contains("  push {r0, ip}\n  pop {r0, ip}\n  str r0, [fp, #-12]\n"));
+    assert!(asm.contains("  pop {r0, ip}\n  str r0, [fp, #-8]\n"));
+    assert!(asm.contains("  ldr r0, [fp, #-12]\n  push {r0, ip}\n  ldr r0, [fp, #-8]\n"));
+    assert!(asm.contains("  add r0, r0, r1\n  str r0, [fp, #-12]\n"));
+    assert!(asm.contains("  ldr r0, =-8\n  add r0, fp, r0\n"));
+}
+
+#[test]
+fn test_compile_sized_integers() {
+    let src = "\
+:main -> int {
+  mut buf: [u8; 6] = [0; 6];
+  buf[1] = (buf[0] + 1) as u8;
+  mut h: i16 = -300;
+  mut s: i8 = -1;
+  mut p: *u8 = &buf[1];
+  *p = 200;
+  mut c = buf[1];
+  ret (c + *p + h + s) as u8 as int;
+}
+";
+    let asm = compile(Source::new("sized.sac", src)).unwrap();
+    // six bytes round up to two words, filled a byte at a time
+    assert!(asm.contains("  ldr r1, =-8\n  add r1, fp, r1\n"));
+    assert!(asm.contains("  strb r0, [r1], #1\n"));
+    assert!(asm.contains("  ldrb r0, [r1, r0]\n"));
+    assert!(asm.contains("  uxtb r0, r0\n  pop {r1, ip}\n"));
+    assert!(asm.contains("  strb r0, [r2, r1]\n"));
+    // narrow locals are extended as they are read
+    assert!(asm.contains("  strh r0, [fp, #-12]\n"));
+    assert!(asm.contains("  ldrsh r0, [fp, #-12]\n"));
+    assert!(asm.contains("  strb r0, [fp, #-16]\n"));
+    assert!(asm.contains("  ldrsb r0, [fp, #-16]\n"));
+    // &buf[1] is the array plus one byte, and *p is a byte
+    assert!(asm.contains("  add r0, r1, r0\n  str r0, [fp, #-20]\n"));
+    assert!(asm.contains("  pop {r2, ip}\n  strb r0, [r2]\n"));
+    assert!(asm.contains("  ldr r0, [fp, #-20]\n  ldrb r0, [r0]\n"));
+    // c is inferred as a u8
+    assert!(asm.contains("  ldrb r0, [fp, #-24]\n"));
+}
+
+#[test]
+fn test_compile_unsigned() {
+    let src = "\
+:main {
+  mut big: u32 = 3000000000;
+  mut n = 1;
+  mut half = big / n;
+  mut rem = big % 7;
+  mut top = big >> 31;
+  mut neg = n >> 31;
+  if big > n { printf(\"%u %u %u\\n\", half, rem, top); }
+  if n - 2 < 0 { ret neg; }
+}
+";
+    let asm = compile(Source::new("unsigned.sac", src)).unwrap();
+    // 3000000000 is past the top of an int, so it only fits as a u32
+    assert!(asm.contains("  ldr r0, =-1294967296\n"));
+    assert!(asm.contains("  udiv r0, r1, r0\n"));
+    assert!(asm.contains("  udiv r2, r1, r0\n  mls r0, r2, r0, r1\n"));
+    assert!(asm.contains("  lsr r0, r1, r0\n"));
+    assert!(asm.contains("  cmp r1, r0\n  movhi r0, #1\n  movls r0, #0\n"));
+    // ints stay signed
+    assert!(asm.contains("  asr r0, r1, r0\n"));
+    assert!(asm.contains("  cmp r1, r0\n  movlt r0, #1\n  movge r0, #0\n"));
+}
+
+#[test]
+fn test_compile_i64() {
+    let src = "\
+:fact n: i64 -> i64 {
+  if n <= 1 { ret 1; }
+  ret n * fact(n - 1);
+}
+:main {
+  mut big = 5000000000;
+  mut q = big / 3 % 7;
+  printf(\"%lld\\n\", fact(20) + q);
+}
+";
+    let asm = compile(Source::new("long.sac", src)).unwrap();
+    // n came in r0:r1, and 1 is sign extended to compare with it
+    assert!(asm.contains("fact:\n  push {fp, lr}\n  mov fp, sp\n  push {r0, r1}\n"));
+    assert!(asm.contains("  ldr r0, =1\n  asr r1, r0, #31\n  pop {r2, r3}\n"));
+    assert!(asm.contains("  subs ip, r0, r2\n  sbcs ip, r1, r3\n  movge r0, #1\n"));
+    assert!(asm.contains("  subs r0, r2, r0\n  sbc r1, r3, r1\n  bl fact\n"));
+    assert!(asm.contains("  umull r0, r1, r2, r0\n  add r1, r1, r3\n"));
+    // a literal too big for 32 bits is split in two
+    assert!(asm.contains("  ldr r0, =705032704\n  ldr r1, =1\n"));
+    assert!(asm.contains("  orrs ip, r0, r1\n  beq .SacLabelTrapDivByZero0\n"));
+    assert!(asm.contains("  bl __aeabi_ldivmod\n  mov r0, r2\n  mov r1, r3\n"));
+    assert!(asm.contains("  adds r0, r2, r0\n  adc r1, r3, r1\n"));
+    // an i64 argument skips r1 for the r2:r3 pair
+    assert!(asm.contains("  str r0, [sp, #8]\n  str r1, [sp, #12]\n  pop {r0, r1, r2, r3}\n"));
+}
+
+#[test]
+fn test_compile_i64_range() {
+    let src = "\
+:main {
+  mut big: i64 = 5000000000;
+  mut n = 0;
+  for i in 0..big { n = n + 1; }
+  ret n;
+}
+";
+    let asm = compile(Source::new("range64.sac", src)).unwrap();
+    // the hidden end of the range keeps all 64 bits of `big`
+    assert!(asm.contains("  str r0, [fp, #-24]\n  str r1, [fp, #-20]\n"));
+    assert!(asm.contains("  subs ip, r2, r0\n  sbcs ip, r3, r1\n  movlt r0, #1\n"));
+}
+
+#[test]
+fn test_compile_i64_args() {
+    let src = "\
+:mix a: int, b: i64, c: int, d: i64, e: int -> i64 { ret a + b + c + d + e; }
+:main { mix(1, 2, 3, 4, 5); }
+";
+    let asm = compile(Source::new("args64.sac", src)).unwrap();
+    // a in r0, b in r2:r3, and then c, d and e on the stack, d 8-byte aligned
+    assert!(asm.contains("  sub sp, sp, #40\n"));
+    for at in [0, 8, 16, 24, 32] {
+        assert!(asm.contains(&format!("  str r0, [sp, #{at}]\n")));
+    }
+    assert!(asm.contains("  bl mix\n  add sp, sp, #24\n"));
+    assert!(asm.contains("  ldr r0, [fp, #-16]\n"));
+    assert!(asm.contains("  ldr r0, [fp, #-8]\n  ldr r1, [fp, #-4]\n"));
+    assert!(asm.contains("  ldr r0, [fp, #8]\n  asr r1, r0, #31\n"));
+    assert!(asm.contains("  ldr r0, [fp, #16]\n  ldr r1, [fp, #20]\n"));
+    assert!(asm.contains("  ldr r0, [fp, #24]\n"));
+
+    // ints widen into i64 parameters, and falling off the end returns a
+    // whole 0
+    let src = "\
+:zero n: i64 -> i64 { }
+:main { mut n = 6; zero(n); }
+";
+    let asm = compile(Source::new("args64.sac", src)).unwrap();
+    assert!(asm.contains("  ldr r0, [fp, #-4]\n  asr r1, r0, #31\n"));
+    assert!(asm.contains("  mov sp, fp\n  mov r0, #0\n  mov r1, #0\n  pop {fp, pc}\n"));
+}
+
+#[test]
+fn test_compile_far_locals() {
+    // 8KB of array pushes the locals after it past what ldr and str reach
+    let src = "\
+struct Point { x, y }
+:main {
+  mut big = [0; 2000];
+  mut x = 1;
+  mut wide: i64 = 2;
+  mut p = Point { x: 3, y: 4 };
+  p.y = x;
+  ret x + p.x + wide as int;
+}
+";
+    let asm = compile(Source::new("far.sac", src)).unwrap();
+    assert!(asm.contains("  ldr ip, =-8004\n  str r0, [fp, ip]\n"));
+    assert!(asm.contains("  ldr ip, =-8004\n  ldr r0, [fp, ip]\n"));
+    assert!(asm
+        .contains("  ldr ip, =-8012\n  str r0, [fp, ip]\n  ldr ip, =-8008\n  str r1, [fp, ip]\n"));
+    assert!(asm.contains("  ldr ip, =-8016\n  str r0, [fp, ip]\n"));
+    assert!(asm.contains("  ldr ip, =-8020\n  ldr r0, [fp, ip]\n"));
+    assert!(!asm.contains("[fp, #-8"));
+}
+
+#[test]
+fn test_compile_far_halfword() {
+    // ldrh and strh only reach 255 bytes from fp
+    let src = ":main {\n  mut big = [0; 70];\n  mut h: u16 = 1;\n  ret h;\n}\n";
+    let asm = compile(Source::new("far.sac", src)).unwrap();
+    assert!(asm.contains("  ldr ip, =-284\n  strh r0, [fp, ip]\n"));
+    assert!(asm.contains("  ldr ip, =-284\n  ldrh r0, [fp, ip]\n"));
+
+    // and so does ldrsb, while strb, ldrb and the word forms reach 4095
+    let src = "\
+:main {
+  mut big = [0; 70];
+  mut c: i8 = 1;
+  mut d: u8 = 2;
+  mut far: i64 = 3;
+  ret c + d + far as int;
+}
+";
+    let asm = compile(Source::new("far.sac", src)).unwrap();
+    assert!(asm.contains("  strb r0, [fp, #-284]\n"));
+    assert!(asm.contains("  ldr ip, =-284\n  ldrsb r0, [fp, ip]\n"));
+    assert!(asm.contains("  ldrb r0, [fp, #-288]\n"));
+    assert!(asm.contains("  str r0, [fp, #-296]\n  str r1, [fp, #-292]\n"));
+}
@@ -1,15 +1,40 @@
+use std::collections::HashMap;
+
+use super::diagnostics::Diagnostic;
 use super::span::Span;
-use crate::codegen::builder::{Builder, Label};
+use super::types::Type;
+use crate::codegen::{
+    builder::{arg_slots, Builder, Label, Local},
+    layout::Layout,
+};
+
+// the registers holding the first 1, 2, 3 or 4 arguments of a call
+const ARG_REGS: [&str; 4] = ["r0", "r0, r1", "r0, r1, r2", "r0, r1, r2, r3"];
+
+// tells the nodes of a tree apart by where they are in memory, which no two
+// share for as long as the tree is not moved or changed. type checking and
+// codegen run on the same borrowed tree, so its types can be keyed by it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
 
 #[allow(dead_code)]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum AST {
     Number {
-        num: i32,
+        num: i64,
         span: Span,
     },
     StrLiteral {
         literal: String,
+        span: Span,
+    },
+    Bool {
+        value: bool,
+        span: Span,
+    },
+    Char {
+        ch: char,
+        span: Span,
     },
     Identifier {
         name: String,
@@ -19,6 +44,29 @@ pub enum AST {
         target: Box<AST>,
         span: Span,
     },
+    Negate {
+        target: Box<AST>,
+        span: Span,
+    },
+    BitNot {
+        target: Box<AST>,
+        span: Span,
+    },
+    // `&target`, where target is a variable, an element or a dereference
+    AddressOf {
+        target: Box<AST>,
+        span: Span,
+    },
+    Deref {
+        target: Box<AST>,
+        span: Span,
+    },
+    // `value as ty`
+    Cast {
+        span: Span,
+        value: Box<AST>,
+        ty: Type,
+    },
     Equals {
         lhs: Box<AST>,
         rhs: Box<AST>,
@@ -27,6 +75,30 @@ pub enum AST {
         lhs: Box<AST>,
         rhs: Box<AST>,
     },
+    LessThan {
+        lhs: Box<AST>,
+        rhs: Box<AST>,
+    },
+    GreaterThan {
+        lhs: Box<AST>,
+        rhs: Box<AST>,
+    },
+    LessEquals {
+        lhs: Box<AST>,
+        rhs: Box<AST>,
+    },
+    GreaterEquals {
+        lhs: Box<AST>,
+        rhs: Box<AST>,
+    },
+    And {
+        lhs: Box<AST>,
+        rhs: Box<AST>,
+    },
+    Or {
+        lhs: Box<AST>,
+        rhs: Box<AST>,
+    },
     Add {
         lhs: Box<AST>,
         rhs: Box<AST>,
@@ -43,6 +115,30 @@ pub enum AST {
         lhs: Box<AST>,
         rhs: Box<AST>,
     },
+    Modulo {
+        lhs: Box<AST>,
+        rhs: Box<AST>,
+    },
+    BitAnd {
+        lhs: Box<AST>,
+        rhs: Box<AST>,
+    },
+    BitOr {
+        lhs: Box<AST>,
+        rhs: Box<AST>,
+    },
+    BitXor {
+        lhs: Box<AST>,
+        rhs: Box<AST>,
+    },
+    ShiftLeft {
+        lhs: Box<AST>,
+        rhs: Box<AST>,
+    },
+    ShiftRight {
+        lhs: Box<AST>,
+        rhs: Box<AST>,
+    },
     Call {
         called: String,
         args: Vec<AST>,
@@ -60,17 +156,21 @@ pub enum AST {
         span: Span,
         condition: Box<AST>,
         then: Box<AST>,
-        c_else: Box<AST>,
+        c_else: Option<Box<AST>>,
     },
+    // `types` has the annotation of each parameter, if it has one
     FunctionDef {
         span: Span,
         name: String,
         params: Vec<String>,
+        types: Vec<Option<Type>>,
+        ret: Option<Type>,
         body: Box<AST>,
     },
     Variable {
         span: Span,
         name: String,
+        ty: Option<Type>,
         value: Box<AST>,
     },
     Assignment {
@@ -78,10 +178,83 @@ pub enum AST {
         name: String,
         value: Box<AST>,
     },
+    // `[value; len]`, only as the value of a `mut`. `len` is a constant
+    Array {
+        span: Span,
+        value: Box<AST>,
+        len: Box<AST>,
+    },
+    Index {
+        span: Span,
+        name: String,
+        index: Box<AST>,
+    },
+    // top level only, `struct name { fields }`
+    StructDef {
+        span: Span,
+        name: String,
+        fields: Vec<String>,
+    },
+    // `name { field: value, ... }`, only as the value of a `mut`
+    StructLit {
+        span: Span,
+        name: String,
+        fields: Vec<(String, AST)>,
+    },
+    // `name.field`, where `name` is a struct local
+    Field {
+        span: Span,
+        name: String,
+        field: String,
+    },
+    FieldAssign {
+        span: Span,
+        name: String,
+        field: String,
+        value: Box<AST>,
+    },
+    // `*target = value`
+    DerefAssign {
+        span: Span,
+        target: Box<AST>,
+        value: Box<AST>,
+    },
+    IndexAssign {
+        span: Span,
+        name: String,
+        index: Box<AST>,
+        value: Box<AST>,
+    },
+    // top level only. a global's value lives in .data or .bss, a constant's
+    // is folded into every use
+    Global {
+        span: Span,
+        name: String,
+        value: Box<AST>,
+    },
+    Const {
+        span: Span,
+        name: String,
+        value: Box<AST>,
+    },
+    // top level only. `extern name;` lets C function `name` be called, with
+    // any arguments
+    Extern {
+        span: Span,
+        name: String,
+    },
+    // `step` runs after the body and on `continue`. `for` loops use it
     WhileLoop {
         span: Span,
         condition: Box<AST>,
         body: Box<AST>,
+        step: Option<Box<AST>>,
+    },
+    Break {
+        span: Span,
+    },
+    Continue {
+        span: Span,
     },
     Error {
         span: Span,
@@ -100,6 +273,16 @@ impl AST {
                     span: _,
                 },
             ) => num == onum,
+            (
+                AST::StrLiteral {
+                    ref literal,
+                    span: _,
+                },
+                AST::StrLiteral {
+                    literal: ref oliteral,
+                    span: _,
+                },
+            ) => literal == oliteral,
             (
                 AST::Identifier { ref name, span: _ },
                 AST::Identifier {
@@ -114,6 +297,119 @@ impl AST {
                     span: _,
                 },
             ) => target.equals(otarget),
+            (
+                AST::Negate { target, span: _ },
+                AST::Negate {
+                    target: otarget,
+                    span: _,
+                },
+            ) => target.equals(otarget),
+            (
+                AST::BitNot { target, span: _ },
+                AST::BitNot {
+                    target: otarget,
+                    span: _,
+                },
+            ) => target.equals(otarget),
+            (
+                AST::AddressOf { target, span: _ },
+                AST::AddressOf {
+                    target: otarget,
+                    span: _,
+                },
+            )
+            | (
+                AST::Deref { target, span: _ },
+                AST::Deref {
+                    target: otarget,
+                    span: _,
+                },
+            ) => target.equals(otarget),
+            (
+                AST::Cast { value, ty, span: _ },
+                AST::Cast {
+                    value: ovalue,
+                    ty: oty,
+                    span: _,
+                },
+            ) => ty == oty && value.equals(ovalue),
+            (
+                AST::Extern { span: _, name },
+                AST::Extern {
+                    span: _,
+                    name: oname,
+                },
+            ) => name == oname,
+            (
+                AST::StructDef {
+                    span: _,
+                    name,
+                    fields,
+                },
+                AST::StructDef {
+                    span: _,
+                    name: oname,
+                    fields: ofields,
+                },
+            ) => name == oname && fields == ofields,
+            (
+                AST::StructLit {
+                    span: _,
+                    name,
+                    fields,
+                },
+                AST::StructLit {
+                    span: _,
+                    name: oname,
+                    fields: ofields,
+                },
+            ) => {
+                name == oname
+                    && fields.len() == ofields.len()
+                    && fields.iter().zip(ofields.iter()).all(
+                        |((field, value), (ofield, ovalue))| {
+                            field == ofield && value.equals(ovalue)
+                        },
+                    )
+            }
+            (
+                AST::Field {
+                    span: _,
+                    name,
+                    field,
+                },
+                AST::Field {
+                    span: _,
+                    name: oname,
+                    field: ofield,
+                },
+            ) => name == oname && field == ofield,
+            (
+                AST::FieldAssign {
+                    span: _,
+                    name,
+                    field,
+                    value,
+                },
+                AST::FieldAssign {
+                    span: _,
+                    name: oname,
+                    field: ofield,
+                    value: ovalue,
+                },
+            ) => name == oname && field == ofield && value.equals(ovalue),
+            (
+                AST::DerefAssign {
+                    span: _,
+                    target,
+                    value,
+                },
+                AST::DerefAssign {
+                    span: _,
+                    target: otarget,
+                    value: ovalue,
+                },
+            ) => target.equals(otarget) && value.equals(ovalue),
             (
                 AST::Equals { lhs, rhs },
                 AST::Equals {
@@ -128,6 +424,48 @@ impl AST {
                     rhs: orhs,
                 },
             ) => lhs.equals(olhs) && rhs.equals(orhs),
+            (
+                AST::LessThan { lhs, rhs },
+                AST::LessThan {
+                    lhs: olhs,
+                    rhs: orhs,
+                },
+            ) => lhs.equals(olhs) && rhs.equals(orhs),
+            (
+                AST::GreaterThan { lhs, rhs },
+                AST::GreaterThan {
+                    lhs: olhs,
+                    rhs: orhs,
+                },
+            ) => lhs.equals(olhs) && rhs.equals(orhs),
+            (
+                AST::LessEquals { lhs, rhs },
+                AST::LessEquals {
+                    lhs: olhs,
+                    rhs: orhs,
+                },
+            ) => lhs.equals(olhs) && rhs.equals(orhs),
+            (
+                AST::GreaterEquals { lhs, rhs },
+                AST::GreaterEquals {
+                    lhs: olhs,
+                    rhs: orhs,
+                },
+            ) => lhs.equals(olhs) && rhs.equals(orhs),
+            (
+                AST::And { lhs, rhs },
+                AST::And {
+                    lhs: olhs,
+                    rhs: orhs,
+                },
+            ) => lhs.equals(olhs) && rhs.equals(orhs),
+            (
+                AST::Or { lhs, rhs },
+                AST::Or {
+                    lhs: olhs,
+                    rhs: orhs,
+                },
+            ) => lhs.equals(olhs) && rhs.equals(orhs),
             (
                 AST::Add { lhs, rhs },
                 AST::Add {
@@ -156,6 +494,48 @@ impl AST {
                     rhs: orhs,
                 },
             ) => lhs.equals(olhs) && rhs.equals(orhs),
+            (
+                AST::Modulo { lhs, rhs },
+                AST::Modulo {
+                    lhs: olhs,
+                    rhs: orhs,
+                },
+            ) => lhs.equals(olhs) && rhs.equals(orhs),
+            (
+                AST::BitAnd { lhs, rhs },
+                AST::BitAnd {
+                    lhs: olhs,
+                    rhs: orhs,
+                },
+            ) => lhs.equals(olhs) && rhs.equals(orhs),
+            (
+                AST::BitOr { lhs, rhs },
+                AST::BitOr {
+                    lhs: olhs,
+                    rhs: orhs,
+                },
+            ) => lhs.equals(olhs) && rhs.equals(orhs),
+            (
+                AST::BitXor { lhs, rhs },
+                AST::BitXor {
+                    lhs: olhs,
+                    rhs: orhs,
+                },
+            ) => lhs.equals(olhs) && rhs.equals(orhs),
+            (
+                AST::ShiftLeft { lhs, rhs },
+                AST::ShiftLeft {
+                    lhs: olhs,
+                    rhs: orhs,
+                },
+            ) => lhs.equals(olhs) && rhs.equals(orhs),
+            (
+                AST::ShiftRight { lhs, rhs },
+                AST::ShiftRight {
+                    lhs: olhs,
+                    rhs: orhs,
+                },
+            ) => lhs.equals(olhs) && rhs.equals(orhs),
             (
                 AST::Call {
                     called,
@@ -211,18 +591,30 @@ impl AST {
                     then: othen,
                     c_else: oc_else,
                 },
-            ) => condition.equals(ocondition) && then.equals(othen) && c_else.equals(oc_else),
+            ) => {
+                condition.equals(ocondition)
+                    && then.equals(othen)
+                    && match (c_else, oc_else) {
+                        (Some(c_else), Some(oc_else)) => c_else.equals(oc_else),
+                        (None, None) => true,
+                        _ => false,
+                    }
+            }
             (
                 AST::FunctionDef {
                     span: _,
                     name,
                     params,
+                    types,
+                    ret,
                     body,
                 },
                 AST::FunctionDef {
                     span: _,
                     name: oname,
                     params: oparams,
+                    types: otypes,
+                    ret: oret,
                     body: obody,
                 },
             ) => {
@@ -232,20 +624,32 @@ impl AST {
                         .iter()
                         .zip(oparams.iter())
                         .all(|(param, oparam)| param == oparam)
+                    && types == otypes
+                    && ret == oret
                     && body.equals(obody)
             }
             (
                 AST::Variable {
                     span: _,
                     name,
+                    ty,
                     value,
                 },
                 AST::Variable {
                     span: _,
                     name: oname,
+                    ty: oty,
                     value: ovalue,
                 },
-            ) => name == oname && value.equals(ovalue),
+            ) => name == oname && ty == oty && value.equals(ovalue),
+            (
+                AST::Bool { value, span: _ },
+                AST::Bool {
+                    value: ovalue,
+                    span: _,
+                },
+            ) => value == ovalue,
+            (AST::Char { ch, span: _ }, AST::Char { ch: och, span: _ }) => ch == och,
             (
                 AST::Assignment {
                     span: _,
@@ -259,35 +663,141 @@ impl AST {
                 },
             ) => name == oname && value.equals(ovalue),
             (
-                AST::WhileLoop {
+                AST::Array {
                     span: _,
-                    condition,
-                    body,
+                    value,
+                    len,
                 },
-                AST::WhileLoop {
+                AST::Array {
                     span: _,
-                    condition: ocondition,
-                    body: obody,
+                    value: ovalue,
+                    len: olen,
                 },
-            ) => condition.equals(ocondition) && body.equals(obody),
-            _ => false,
-        }
-    }
-
-    pub fn get_span(&self) -> Span {
-        match self {
+            ) => value.equals(ovalue) && len.equals(olen),
+            (
+                AST::Index {
+                    span: _,
+                    name,
+                    index,
+                },
+                AST::Index {
+                    span: _,
+                    name: oname,
+                    index: oindex,
+                },
+            ) => name == oname && index.equals(oindex),
+            (
+                AST::IndexAssign {
+                    span: _,
+                    name,
+                    index,
+                    value,
+                },
+                AST::IndexAssign {
+                    span: _,
+                    name: oname,
+                    index: oindex,
+                    value: ovalue,
+                },
+            ) => name == oname && index.equals(oindex) && value.equals(ovalue),
+            (
+                AST::Global {
+                    span: _,
+                    name,
+                    value,
+                },
+                AST::Global {
+                    span: _,
+                    name: oname,
+                    value: ovalue,
+                },
+            )
+            | (
+                AST::Const {
+                    span: _,
+                    name,
+                    value,
+                },
+                AST::Const {
+                    span: _,
+                    name: oname,
+                    value: ovalue,
+                },
+            ) => name == oname && value.equals(ovalue),
+            (
+                AST::WhileLoop {
+                    span: _,
+                    condition,
+                    body,
+                    step,
+                },
+                AST::WhileLoop {
+                    span: _,
+                    condition: ocondition,
+                    body: obody,
+                    step: ostep,
+                },
+            ) => {
+                condition.equals(ocondition)
+                    && body.equals(obody)
+                    && match (step, ostep) {
+                        (Some(step), Some(ostep)) => step.equals(ostep),
+                        (None, None) => true,
+                        _ => false,
+                    }
+            }
+            (AST::Break { span: _ }, AST::Break { span: _ }) => true,
+            (AST::Continue { span: _ }, AST::Continue { span: _ }) => true,
+            _ => false,
+        }
+    }
+
+    pub fn id(&self) -> NodeId {
+        NodeId(self as *const AST as usize)
+    }
+
+    pub fn get_span(&self) -> Span {
+        match self {
             AST::Number { num: _, ref span } => span.clone(),
             AST::Identifier { name: _, ref span } => span.clone(),
             AST::Not {
                 target: _,
                 ref span,
             } => span.clone(),
+            AST::Negate {
+                target: _,
+                ref span,
+            } => span.clone(),
+            AST::BitNot {
+                target: _,
+                ref span,
+            } => span.clone(),
+            AST::AddressOf { target: _, span }
+            | AST::Deref { target: _, span }
+            | AST::DerefAssign { span, .. }
+            | AST::Cast { span, .. } => span.clone(),
+            AST::StructDef { span, .. }
+            | AST::StructLit { span, .. }
+            | AST::Field { span, .. }
+            | AST::FieldAssign { span, .. } => span.clone(),
             AST::Equals { lhs, rhs } => lhs.get_span().merge_with(&rhs.get_span()),
             AST::NEquals { lhs, rhs } => lhs.get_span().merge_with(&rhs.get_span()),
+            AST::LessThan { lhs, rhs } => lhs.get_span().merge_with(&rhs.get_span()),
+            AST::GreaterThan { lhs, rhs } => lhs.get_span().merge_with(&rhs.get_span()),
+            AST::LessEquals { lhs, rhs } => lhs.get_span().merge_with(&rhs.get_span()),
+            AST::GreaterEquals { lhs, rhs } => lhs.get_span().merge_with(&rhs.get_span()),
+            AST::And { lhs, rhs } => lhs.get_span().merge_with(&rhs.get_span()),
+            AST::Or { lhs, rhs } => lhs.get_span().merge_with(&rhs.get_span()),
             AST::Add { lhs, rhs } => lhs.get_span().merge_with(&rhs.get_span()),
             AST::Subtract { lhs, rhs } => lhs.get_span().merge_with(&rhs.get_span()),
             AST::Multiply { lhs, rhs } => lhs.get_span().merge_with(&rhs.get_span()),
             AST::Divide { lhs, rhs } => lhs.get_span().merge_with(&rhs.get_span()),
+            AST::Modulo { lhs, rhs } => lhs.get_span().merge_with(&rhs.get_span()),
+            AST::BitAnd { lhs, rhs } => lhs.get_span().merge_with(&rhs.get_span()),
+            AST::BitOr { lhs, rhs } => lhs.get_span().merge_with(&rhs.get_span()),
+            AST::BitXor { lhs, rhs } => lhs.get_span().merge_with(&rhs.get_span()),
+            AST::ShiftLeft { lhs, rhs } => lhs.get_span().merge_with(&rhs.get_span()),
+            AST::ShiftRight { lhs, rhs } => lhs.get_span().merge_with(&rhs.get_span()),
             AST::Call {
                 called: _,
                 args: _,
@@ -304,120 +814,640 @@ impl AST {
                 then: _,
                 c_else: _,
             } => span.clone(),
-            AST::FunctionDef {
-                span,
-                name: _,
-                params: _,
-                body: _,
-            } => span.clone(),
-            AST::Variable {
-                span,
-                name: _,
-                value: _,
-            } => span.clone(),
+            AST::FunctionDef { span, .. } => span.clone(),
+            AST::Variable { span, .. } => span.clone(),
+            AST::Bool { span, .. } | AST::Char { span, .. } => span.clone(),
             AST::Assignment {
                 span,
                 name: _,
                 value: _,
             } => span.clone(),
+            AST::Array { span, .. } | AST::Index { span, .. } | AST::IndexAssign { span, .. } => {
+                span.clone()
+            }
+            AST::Global { span, .. } | AST::Const { span, .. } | AST::Extern { span, .. } => {
+                span.clone()
+            }
             AST::WhileLoop {
                 span,
                 condition: _,
                 body: _,
+                step: _,
             } => span.clone(),
             AST::Error { span, msg: _ } => span.clone(),
-            AST::StrLiteral { literal: _ } => Span::new_dud(),
+            AST::Break { span } | AST::Continue { span } => span.clone(),
+            AST::StrLiteral { literal: _, span } => span.clone(),
         }
     }
 
-    pub fn emit_arm32(&self, b: &mut Builder) {
+    // the nodes directly under this one, in source order
+    pub fn children(&self) -> Vec<&AST> {
         match self {
+            AST::Number { .. }
+            | AST::StrLiteral { .. }
+            | AST::Bool { .. }
+            | AST::Char { .. }
+            | AST::Identifier { .. }
+            | AST::Error { .. }
+            | AST::Break { .. }
+            | AST::Continue { .. }
+            | AST::StructDef { .. }
+            | AST::Extern { .. }
+            | AST::Field { .. } => vec![],
+            AST::Not { target, span: _ }
+            | AST::Negate { target, span: _ }
+            | AST::BitNot { target, span: _ }
+            | AST::AddressOf { target, span: _ }
+            | AST::Deref { target, span: _ } => vec![target],
+            AST::DerefAssign { target, value, .. } => vec![target, value],
+            AST::Cast { value, .. } => vec![value],
+            AST::StructLit { fields, .. } => fields.iter().map(|(_, value)| value).collect(),
+            AST::FieldAssign { value, .. } => vec![value],
+            AST::Equals { lhs, rhs }
+            | AST::NEquals { lhs, rhs }
+            | AST::LessThan { lhs, rhs }
+            | AST::GreaterThan { lhs, rhs }
+            | AST::LessEquals { lhs, rhs }
+            | AST::GreaterEquals { lhs, rhs }
+            | AST::And { lhs, rhs }
+            | AST::Or { lhs, rhs }
+            | AST::Add { lhs, rhs }
+            | AST::Subtract { lhs, rhs }
+            | AST::Multiply { lhs, rhs }
+            | AST::Divide { lhs, rhs }
+            | AST::Modulo { lhs, rhs }
+            | AST::BitAnd { lhs, rhs }
+            | AST::BitOr { lhs, rhs }
+            | AST::BitXor { lhs, rhs }
+            | AST::ShiftLeft { lhs, rhs }
+            | AST::ShiftRight { lhs, rhs } => vec![lhs, rhs],
+            AST::Call { args, .. } => args.iter().collect(),
+            AST::Return { value, span: _ } => vec![value],
             AST::Block {
                 statements,
                 span: _,
-            } => statements.iter().for_each(|stmt| {
-                stmt.emit_arm32(b);
+            } => statements.iter().collect(),
+            AST::IfCond {
+                condition,
+                then,
+                c_else,
+                ..
+            } => {
+                let mut children = vec![condition.as_ref(), then];
+                children.extend(c_else.as_deref());
+                children
+            }
+            AST::FunctionDef { body, .. } => vec![body],
+            AST::Variable { value, .. } => vec![value],
+            AST::Assignment { value, .. } => vec![value],
+            AST::Array { value, len, .. } => vec![value, len],
+            AST::Index { index, .. } => vec![index],
+            AST::IndexAssign { index, value, .. } => vec![index, value],
+            AST::Global { value, .. } | AST::Const { value, .. } => vec![value],
+            AST::WhileLoop {
+                condition,
+                body,
+                step,
+                ..
+            } => {
+                let mut children = vec![condition.as_ref(), body];
+                children.extend(step.as_deref());
+                children
+            }
+        }
+    }
+
+    // the value of a constant expression: numbers, constants in `consts` and
+    // operators on them. None for anything only known at runtime, and for
+    // division by zero, which is left for the trap to report. callers leave
+    // out the constants a local shadows
+    pub fn fold(&self, consts: &HashMap<String, i32>) -> Option<i32> {
+        let both = |lhs: &AST, rhs: &AST| Some((lhs.fold(consts)?, rhs.fold(consts)?));
+        match self {
+            // literals that need 64 bits are not folded
+            AST::Number { num, span: _ } => i32::try_from(*num).ok(),
+            AST::Bool { value, span: _ } => Some(*value as i32),
+            AST::Char { ch, span: _ } => Some(*ch as i32),
+            AST::Identifier { name, span: _ } => consts.get(name).copied(),
+            AST::Not { target, span: _ } => Some((target.fold(consts)? == 0) as i32),
+            AST::Negate { target, span: _ } => Some(target.fold(consts)?.wrapping_neg()),
+            AST::BitNot { target, span: _ } => Some(!target.fold(consts)?),
+            AST::Cast { value, ty, .. } => Some(ty.wrap(value.fold(consts)?)),
+            AST::Equals { lhs, rhs } => both(lhs, rhs).map(|(l, r)| (l == r) as i32),
+            AST::NEquals { lhs, rhs } => both(lhs, rhs).map(|(l, r)| (l != r) as i32),
+            AST::LessThan { lhs, rhs } => both(lhs, rhs).map(|(l, r)| (l < r) as i32),
+            AST::GreaterThan { lhs, rhs } => both(lhs, rhs).map(|(l, r)| (l > r) as i32),
+            AST::LessEquals { lhs, rhs } => both(lhs, rhs).map(|(l, r)| (l <= r) as i32),
+            AST::GreaterEquals { lhs, rhs } => both(lhs, rhs).map(|(l, r)| (l >= r) as i32),
+            AST::And { lhs, rhs } => both(lhs, rhs).map(|(l, r)| (l != 0 && r != 0) as i32),
+            AST::Or { lhs, rhs } => both(lhs, rhs).map(|(l, r)| (l != 0 || r != 0) as i32),
+            AST::Add { lhs, rhs } => both(lhs, rhs).map(|(l, r)| l.wrapping_add(r)),
+            AST::Subtract { lhs, rhs } => both(lhs, rhs).map(|(l, r)| l.wrapping_sub(r)),
+            AST::Multiply { lhs, rhs } => both(lhs, rhs).map(|(l, r)| l.wrapping_mul(r)),
+            AST::Divide { lhs, rhs } => both(lhs, rhs).and_then(|(l, r)| l.checked_div(r)),
+            AST::Modulo { lhs, rhs } => both(lhs, rhs).and_then(|(l, r)| l.checked_rem(r)),
+            AST::BitAnd { lhs, rhs } => both(lhs, rhs).map(|(l, r)| l & r),
+            AST::BitOr { lhs, rhs } => both(lhs, rhs).map(|(l, r)| l | r),
+            AST::BitXor { lhs, rhs } => both(lhs, rhs).map(|(l, r)| l ^ r),
+            // the shift amount is the bottom byte of the register, as for lsl
+            AST::ShiftLeft { lhs, rhs } => {
+                both(lhs, rhs).map(|(l, r)| l.checked_shl(r as u8 as u32).unwrap_or(0))
+            }
+            AST::ShiftRight { lhs, rhs } => both(lhs, rhs).map(|(l, r)| {
+                l.checked_shr(r as u8 as u32)
+                    .unwrap_or(if l < 0 { -1 } else { 0 })
             }),
+            _ => None,
+        }
+    }
+
+    // folds the globals and constants declared at the top level of a file,
+    // in order, lays out its structs and notes its functions' signatures, so
+    // every function can use them wherever it is
+    pub fn declare_globals(&self, b: &mut Builder) -> Result<(), Diagnostic> {
+        for stmt in self.children() {
+            match stmt {
+                AST::Global { span, name, value } => {
+                    let value = value
+                        .fold(&b.consts())
+                        .ok_or_else(|| not_constant(name, span))?;
+                    b.add_global(name.clone(), value);
+                }
+                AST::Const { span, name, value } => {
+                    let value = value
+                        .fold(&b.consts())
+                        .ok_or_else(|| not_constant(name, span))?;
+                    b.add_const(name.clone(), value);
+                }
+                AST::StructDef { name, fields, .. } => {
+                    b.add_struct(name.clone(), Layout::n(fields));
+                }
+                AST::FunctionDef {
+                    name, types, ret, ..
+                } => {
+                    let params = types.iter().map(annotated).collect();
+                    b.add_function(name.clone(), params, annotated(ret));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // every AST::Error left behind by the parser, as diagnostics. codegen
+    // only runs on trees where this is empty
+    pub fn errors(&self) -> Vec<Diagnostic> {
+        match self {
+            AST::Error { span, msg } => vec![Diagnostic::error(span.clone(), msg.clone())],
+            _ => self
+                .children()
+                .into_iter()
+                .flat_map(|child| child.errors())
+                .collect(),
+        }
+    }
+
+    pub fn emit_arm32(&self, b: &mut Builder) -> Result<(), Diagnostic> {
+        match self {
+            AST::Block {
+                statements,
+                span: _,
+            } => {
+                // the block's locals go out of scope, and their slots are
+                // free again, when it ends
+                b.enter_ctx();
+                for stmt in statements {
+                    stmt.emit_arm32(b)?;
+                }
+                b.exit_ctx()
+                    .map_err(|msg| Diagnostic::error(self.get_span(), msg))?;
+            }
             AST::Not { target, span: _ } => {
-                target.emit_arm32(b);
+                target.emit_arm32(b)?;
                 b.add("  cmp r0, #0");
                 b.add("  moveq r0, #1");
                 b.add("  movne r0, #0");
             }
+            AST::Negate { target, span: _ } if wide(b, self) => {
+                emit_wide(target, b)?;
+                b.add("  rsbs r0, r0, #0");
+                b.add("  rsc r1, r1, #0");
+            }
+            AST::Negate { target, span: _ } => {
+                target.emit_arm32(b)?;
+                b.add("  rsb r0, r0, #0");
+            }
+            AST::BitNot { target, span: _ } if wide(b, self) => {
+                emit_wide(target, b)?;
+                b.add("  mvn r0, r0");
+                b.add("  mvn r1, r1");
+            }
+            AST::BitNot { target, span: _ } => {
+                target.emit_arm32(b)?;
+                b.add("  mvn r0, r0");
+            }
+            AST::AddressOf { target, span } => match target.as_ref() {
+                AST::Identifier { name, span } => {
+                    if let Some(local) = b.try_get(name) {
+                        b.add(&format!("  ldr r0, ={}", local.offset));
+                        b.add("  add r0, fp, r0");
+                    } else if let Some(label) = b.try_get_global(name) {
+                        b.add(&format!("  ldr r0, ={}", label.s()));
+                    } else {
+                        return Err(undefined_variable(name, span));
+                    }
+                }
+                AST::Index { span, name, index } => {
+                    let (offset, of, len) = array_local(b, name, span)?;
+                    index.emit_arm32(b)?;
+                    bounds_check(b, "r0", len);
+                    b.add(&format!("  ldr r1, ={offset}"));
+                    b.add("  add r1, fp, r1");
+                    match of.size() {
+                        1 => b.add("  add r0, r1, r0"),
+                        2 => b.add("  add r0, r1, r0, lsl #1"),
+                        8 => b.add("  add r0, r1, r0, lsl #3"),
+                        _ => b.add("  add r0, r1, r0, lsl #2"),
+                    }
+                }
+                AST::Field { span, name, field } => {
+                    let offset = field_offset(b, name, field, span)?;
+                    b.add(&format!("  ldr r0, ={offset}"));
+                    b.add("  add r0, fp, r0");
+                }
+                // &*p is p
+                AST::Deref { target, .. } => target.emit_arm32(b)?,
+                _ => return Err(temporary_address(span)),
+            },
+            AST::Deref { target, .. } => {
+                target.emit_arm32(b)?;
+                match b.type_of(self) {
+                    Type::I64 => {
+                        b.add("  ldr r1, [r0, #4]");
+                        b.add("  ldr r0, [r0]");
+                    }
+                    ty => b.add(&format!("  {} r0, [r0]", ty.load())),
+                }
+            }
+            AST::DerefAssign { target, value, .. } => {
+                let ty = b.type_of(self);
+                target.emit_arm32(b)?;
+                b.add("  push {r0, ip}");
+                emit_as(value, &ty, b)?;
+                // r1 may hold the value's high word
+                b.add("  pop {r2, ip}");
+                b.add(&format!("  {} r0, [r2]", ty.store()));
+                if ty == Type::I64 {
+                    b.add("  str r1, [r2, #4]");
+                }
+            }
+            AST::Cast {
+                value,
+                ty: Type::I64,
+                ..
+            } => emit_wide(value, b)?,
+            AST::Cast { value, ty, .. } => {
+                value.emit_arm32(b)?;
+                // the value is a full word, or an i64 whose low word is in
+                // r0. narrowing drops the high bits and extends what is left
+                match ty {
+                    Type::I8 => b.add("  sxtb r0, r0"),
+                    Type::U8 => b.add("  uxtb r0, r0"),
+                    Type::I16 => b.add("  sxth r0, r0"),
+                    Type::U16 => b.add("  uxth r0, r0"),
+                    _ => {}
+                }
+            }
+            // 64-bit operations, with lhs in r2:r3 and rhs in r0:r1
+            AST::Add { lhs, rhs } if wide(b, self) => {
+                wide_operands(b, lhs, rhs)?;
+                b.add("  adds r0, r2, r0");
+                b.add("  adc r1, r3, r1");
+            }
+            AST::Subtract { lhs, rhs } if wide(b, self) => {
+                wide_operands(b, lhs, rhs)?;
+                b.add("  subs r0, r2, r0");
+                b.add("  sbc r1, r3, r1");
+            }
+            AST::Multiply { lhs, rhs } if wide(b, self) => {
+                wide_operands(b, lhs, rhs)?;
+                // the low words' full product, plus the cross products in
+                // the high word. what overflows 64 bits is dropped
+                b.add("  mul r3, r3, r0");
+                b.add("  mla r3, r2, r1, r3");
+                b.add("  umull r0, r1, r2, r0");
+                b.add("  add r1, r1, r3");
+            }
+            AST::Divide { lhs, rhs } | AST::Modulo { lhs, rhs } if wide(b, self) => {
+                wide_operands(b, lhs, rhs)?;
+                let div_by_zero = b.trap("DivByZero", "division by zero");
+                b.add("  orrs ip, r0, r1");
+                b.add(&format!("  beq {}", div_by_zero.s()));
+                // the runtime helper divides r0:r1 by r2:r3, leaving the
+                // quotient in r0:r1 and the remainder in r2:r3
+                b.add("  push {r0, r1}");
+                b.add("  mov r0, r2");
+                b.add("  mov r1, r3");
+                b.add("  pop {r2, r3}");
+                b.add("  bl __aeabi_ldivmod");
+                if let AST::Modulo { .. } = self {
+                    b.add("  mov r0, r2");
+                    b.add("  mov r1, r3");
+                }
+            }
+            AST::BitAnd { lhs, rhs } | AST::BitOr { lhs, rhs } | AST::BitXor { lhs, rhs }
+                if wide(b, self) =>
+            {
+                wide_operands(b, lhs, rhs)?;
+                let op = match self {
+                    AST::BitAnd { .. } => "and",
+                    AST::BitOr { .. } => "orr",
+                    _ => "eor",
+                };
+                b.add(&format!("  {op} r0, r2, r0"));
+                b.add(&format!("  {op} r1, r3, r1"));
+            }
+            AST::ShiftLeft { lhs, rhs } | AST::ShiftRight { lhs, rhs } if wide(b, self) => {
+                emit_wide(lhs, b)?;
+                b.add("  push {r0, r1}");
+                // the amount is a word, or an i64's low word
+                rhs.emit_arm32(b)?;
+                b.add("  mov r2, r0");
+                b.add("  pop {r0, r1}");
+                match self {
+                    AST::ShiftLeft { .. } => b.add("  bl __aeabi_llsl"),
+                    _ => b.add("  bl __aeabi_lasr"),
+                }
+            }
+            AST::Equals { lhs, rhs } | AST::NEquals { lhs, rhs }
+                if wide(b, lhs) || wide(b, rhs) =>
+            {
+                wide_operands(b, lhs, rhs)?;
+                b.add("  cmp r2, r0");
+                b.add("  cmpeq r3, r1");
+                match self {
+                    AST::Equals { .. } => {
+                        b.add("  moveq r0, #1");
+                        b.add("  movne r0, #0");
+                    }
+                    _ => {
+                        b.add("  moveq r0, #0");
+                        b.add("  movne r0, #1");
+                    }
+                }
+            }
+            AST::LessThan { lhs, rhs }
+            | AST::GreaterThan { lhs, rhs }
+            | AST::LessEquals { lhs, rhs }
+            | AST::GreaterEquals { lhs, rhs }
+                if wide(b, lhs) || wide(b, rhs) =>
+            {
+                wide_operands(b, lhs, rhs)?;
+                // the flags of a 64-bit subtraction. lhs > rhs is rhs < lhs
+                let (sub, sbc) = match self {
+                    AST::LessThan { .. } | AST::GreaterEquals { .. } => {
+                        ("  subs ip, r2, r0", "  sbcs ip, r3, r1")
+                    }
+                    _ => ("  subs ip, r0, r2", "  sbcs ip, r1, r3"),
+                };
+                b.add(sub);
+                b.add(sbc);
+                match self {
+                    AST::LessThan { .. } | AST::GreaterThan { .. } => {
+                        b.add("  movlt r0, #1");
+                        b.add("  movge r0, #0");
+                    }
+                    _ => {
+                        b.add("  movge r0, #1");
+                        b.add("  movlt r0, #0");
+                    }
+                }
+            }
+            AST::BitAnd { lhs, rhs } => {
+                lhs.emit_arm32(b)?;
+                b.add("  push {r0, ip}"); // stores r0 to be later used by r1
+                rhs.emit_arm32(b)?;
+                b.add("  pop {r1, ip}"); // gets r1 value
+                b.add("  and r0, r1, r0");
+            }
+            AST::BitOr { lhs, rhs } => {
+                lhs.emit_arm32(b)?;
+                b.add("  push {r0, ip}"); // stores r0 to be later used by r1
+                rhs.emit_arm32(b)?;
+                b.add("  pop {r1, ip}"); // gets r1 value
+                b.add("  orr r0, r1, r0");
+            }
+            AST::BitXor { lhs, rhs } => {
+                lhs.emit_arm32(b)?;
+                b.add("  push {r0, ip}"); // stores r0 to be later used by r1
+                rhs.emit_arm32(b)?;
+                b.add("  pop {r1, ip}"); // gets r1 value
+                b.add("  eor r0, r1, r0");
+            }
+            AST::ShiftLeft { lhs, rhs } => {
+                lhs.emit_arm32(b)?;
+                b.add("  push {r0, ip}"); // stores r0 to be later used by r1
+                rhs.emit_arm32(b)?;
+                b.add("  pop {r1, ip}"); // gets r1 value
+                b.add("  lsl r0, r1, r0");
+            }
+            AST::ShiftRight { lhs, rhs } => {
+                lhs.emit_arm32(b)?;
+                b.add("  push {r0, ip}"); // stores r0 to be later used by r1
+                rhs.emit_arm32(b)?;
+                b.add("  pop {r1, ip}"); // gets r1 value
+
+                // shift right arithmetically so negative numbers keep their
+                // sign, but logically for a u32
+                let shift = if unsigned(b, self) { "lsr" } else { "asr" };
+                b.add(&format!("  {shift} r0, r1, r0"));
+            }
+            AST::And { lhs, rhs } => {
+                // rhs only runs when lhs is true. a false lhs leaves 0 in r0
+                let end = Label::n();
+                lhs.emit_arm32(b)?;
+                b.add("  cmp r0, #0");
+                b.add(&format!("  beq {}", end.s()));
+                rhs.emit_arm32(b)?;
+                b.add("  cmp r0, #0");
+                b.add("  movne r0, #1");
+                b.add(&format!("{}:", end.s()));
+            }
+            AST::Or { lhs, rhs } => {
+                // rhs only runs when lhs is false
+                let end = Label::n();
+                lhs.emit_arm32(b)?;
+                b.add("  cmp r0, #0");
+                b.add("  movne r0, #1");
+                b.add(&format!("  bne {}", end.s()));
+                rhs.emit_arm32(b)?;
+                b.add("  cmp r0, #0");
+                b.add("  movne r0, #1");
+                b.add(&format!("{}:", end.s()));
+            }
             AST::Add { lhs, rhs } => {
-                lhs.emit_arm32(b);
+                lhs.emit_arm32(b)?;
                 b.add("  push {r0, ip}"); // stores r0 to be later used by r1
-                rhs.emit_arm32(b);
+                rhs.emit_arm32(b)?;
                 b.add("  pop {r1, ip}"); // gets r1 value
                 b.add("  add r0, r0, r1");
             }
             AST::Subtract { lhs, rhs } => {
-                lhs.emit_arm32(b);
+                lhs.emit_arm32(b)?;
                 b.add("  push {r0, ip}"); // stores r0 to be later used by r1
-                rhs.emit_arm32(b);
+                rhs.emit_arm32(b)?;
                 b.add("  pop {r1, ip}"); // gets r1 value
                 b.add("  sub r0, r1, r0");
             }
             AST::Multiply { lhs, rhs } => {
-                lhs.emit_arm32(b);
+                lhs.emit_arm32(b)?;
                 b.add("  push {r0, ip}"); // stores r0 to be later used by r1
-                rhs.emit_arm32(b);
+                rhs.emit_arm32(b)?;
                 b.add("  pop {r1, ip}"); // gets r1 value
                 b.add("  mul r2, r0, r1");
                 b.add("  mov r0, r2");
             }
             AST::Divide { lhs, rhs } => {
-                lhs.emit_arm32(b);
+                lhs.emit_arm32(b)?;
                 b.add("  push {r0, ip}"); // stores r0 to be later used by r1
-                rhs.emit_arm32(b);
+                rhs.emit_arm32(b)?;
                 b.add("  pop {r1, ip}"); // gets r1 value
-                b.add("  udiv r0, r1, r0");
+                let div_by_zero = b.trap("DivByZero", "division by zero");
+                b.add("  cmp r0, #0");
+                b.add(&format!("  beq {}", div_by_zero.s()));
+                let div = if unsigned(b, self) { "udiv" } else { "sdiv" };
+                b.add(&format!("  {div} r0, r1, r0"));
+            }
+            AST::Modulo { lhs, rhs } => {
+                lhs.emit_arm32(b)?;
+                b.add("  push {r0, ip}"); // stores r0 to be later used by r1
+                rhs.emit_arm32(b)?;
+                b.add("  pop {r1, ip}"); // gets r1 value
+                let div_by_zero = b.trap("DivByZero", "division by zero");
+                b.add("  cmp r0, #0");
+                b.add(&format!("  beq {}", div_by_zero.s()));
+                // the remainder takes the sign of the lhs, like C
+                let div = if unsigned(b, self) { "udiv" } else { "sdiv" };
+                b.add(&format!("  {div} r2, r1, r0"));
+                b.add("  mls r0, r2, r0, r1");
             }
             AST::Equals { lhs, rhs } => {
-                lhs.emit_arm32(b);
+                lhs.emit_arm32(b)?;
                 b.add("  push {r0, ip}"); // stores r0 to be later used by r1
-                rhs.emit_arm32(b);
+                rhs.emit_arm32(b)?;
                 b.add("  pop {r1, ip}"); // gets r1 value
                 b.add("  cmp r0, r1");
                 b.add("  moveq r0, #1");
                 b.add("  movne r0, #0");
             }
             AST::NEquals { lhs, rhs } => {
-                lhs.emit_arm32(b);
+                lhs.emit_arm32(b)?;
                 b.add("  push {r0, ip}"); // stores r0 to be later used by r1
-                rhs.emit_arm32(b);
+                rhs.emit_arm32(b)?;
                 b.add("  pop {r1, ip}"); // gets r1 value
                 b.add("  cmp r0, r1");
                 b.add("  moveq r0, #0");
                 b.add("  movne r0, #1");
             }
-            AST::Number { num, span: _ } => b.add(format!("  ldr r0, ={num}").as_str()),
+            AST::LessThan { lhs, rhs } => {
+                let (t, f) = conditions(b, lhs, rhs, "lt", "ge");
+                lhs.emit_arm32(b)?;
+                b.add("  push {r0, ip}"); // stores r0 to be later used by r1
+                rhs.emit_arm32(b)?;
+                b.add("  pop {r1, ip}"); // gets r1 value
+                b.add("  cmp r1, r0"); // lhs against rhs
+                b.add(&format!("  mov{t} r0, #1"));
+                b.add(&format!("  mov{f} r0, #0"));
+            }
+            AST::GreaterThan { lhs, rhs } => {
+                let (t, f) = conditions(b, lhs, rhs, "gt", "le");
+                lhs.emit_arm32(b)?;
+                b.add("  push {r0, ip}"); // stores r0 to be later used by r1
+                rhs.emit_arm32(b)?;
+                b.add("  pop {r1, ip}"); // gets r1 value
+                b.add("  cmp r1, r0"); // lhs against rhs
+                b.add(&format!("  mov{t} r0, #1"));
+                b.add(&format!("  mov{f} r0, #0"));
+            }
+            AST::LessEquals { lhs, rhs } => {
+                let (t, f) = conditions(b, lhs, rhs, "le", "gt");
+                lhs.emit_arm32(b)?;
+                b.add("  push {r0, ip}"); // stores r0 to be later used by r1
+                rhs.emit_arm32(b)?;
+                b.add("  pop {r1, ip}"); // gets r1 value
+                b.add("  cmp r1, r0"); // lhs against rhs
+                b.add(&format!("  mov{t} r0, #1"));
+                b.add(&format!("  mov{f} r0, #0"));
+            }
+            AST::GreaterEquals { lhs, rhs } => {
+                let (t, f) = conditions(b, lhs, rhs, "ge", "lt");
+                lhs.emit_arm32(b)?;
+                b.add("  push {r0, ip}"); // stores r0 to be later used by r1
+                rhs.emit_arm32(b)?;
+                b.add("  pop {r1, ip}"); // gets r1 value
+                b.add("  cmp r1, r0"); // lhs against rhs
+                b.add(&format!("  mov{t} r0, #1"));
+                b.add(&format!("  mov{f} r0, #0"));
+            }
+            AST::Number { num, span: _ } => match i32::try_from(*num) {
+                Ok(num) => b.add(format!("  ldr r0, ={num}").as_str()),
+                // the low word in r0, the high one in r1
+                Err(_) => {
+                    b.add(&format!("  ldr r0, ={}", *num as i32));
+                    b.add(&format!("  ldr r1, ={}", (*num >> 32) as i32));
+                }
+            },
+            AST::Bool { value, span: _ } => b.add(&format!("  ldr r0, ={}", *value as i32)),
+            AST::Char { ch, span: _ } => b.add(&format!("  ldr r0, ={}", *ch as i32)),
             AST::Call {
                 called,
                 args,
                 span: _,
-            } => match args.len() {
-                0 => b.add(&format!("  bl {called}")),
-                1 => {
-                    args[0].emit_arm32(b);
-                    b.add(&format!("  bl {called}"));
-                }
-                len if len > 1 && len <= 4 => {
-                    b.add(&format!("  sub sp, sp, #{}", len * 4));
-                    for (i, arg) in args.iter().enumerate() {
-                        arg.emit_arm32(b);
-                        b.add(&format!("  str r0, [sp, #{}]", i * 4));
+            } => {
+                // a sac function's parameters decide how its arguments are
+                // passed, a C function gets them as they are
+                let types: Vec<_> = match b.try_get_params(called) {
+                    Some(params) => params.to_vec(),
+                    None => args.iter().map(|arg| b.type_of(arg)).collect(),
+                };
+                match args.len() {
+                    0 => b.add(&format!("  bl {called}")),
+                    1 => {
+                        emit_as(&args[0], &types[0], b)?;
+                        b.add(&format!("  bl {called}"));
+                    }
+                    _ => {
+                        // each argument is written where arg_slots puts it.
+                        // the ones going in registers are popped into them,
+                        // the rest stay on the stack, where the callee finds
+                        // them above its frame. the register part is padded
+                        // to 8 bytes so sp stays 8-byte aligned while the
+                        // arguments are generated, calls in them included.
+                        // the padding goes to the register after the last
+                        // argument, which is free
+                        let (slots, in_regs, on_stack) = arg_slots(&types);
+                        let in_regs_span = (in_regs as i32 * 4 + 7) / 8 * 8;
+                        b.add(&format!("  sub sp, sp, #{}", in_regs_span + on_stack));
+                        for ((arg, ty), slot) in args.iter().zip(&types).zip(slots) {
+                            emit_as(arg, ty, b)?;
+                            let at = match slot < 16 {
+                                true => slot,
+                                false => in_regs_span + slot - 16,
+                            };
+                            b.add(&format!("  str r0, [sp, #{at}]"));
+                            if ty == &Type::I64 {
+                                b.add(&format!("  str r1, [sp, #{}]", at + 4));
+                            }
+                        }
+                        let popped = in_regs_span as usize / 4;
+                        b.add(&format!("  pop {{{}}}", ARG_REGS[popped - 1]));
+                        b.add(&format!("  bl {called}"));
+                        if on_stack > 0 {
+                            b.add(&format!("  add sp, sp, #{on_stack}"));
+                        }
                     }
-                    let regs_list = vec!["r0", "r0, r1", "r0, r1, r2", "r0, r1, r2, r3"];
-                    let regs = regs_list[len - 1];
-
-                    b.add(&format!("  pop {{{regs}}}"));
-                    b.add(&format!("  bl {called}"));
-                }
-                _ => {
-                    panic!("More than 4 arguments is not supported :(");
                 }
-            },
+            }
             AST::IfCond {
                 span: _,
                 condition,
@@ -425,25 +1455,30 @@ impl AST {
                 c_else,
             } => {
                 let if_false_label = Label::n();
-                let end_if_label = Label::n();
-                condition.emit_arm32(b);
+                condition.emit_arm32(b)?;
                 b.add("  cmp r0, #0");
                 b.add(&format!("  beq {}", if_false_label.s()));
-                then.emit_arm32(b);
-                b.add(&format!("  b {}", end_if_label.s()));
-                b.add(&format!("{}:", if_false_label.s()));
-                c_else.emit_arm32(b);
-                b.add(&format!("{}:", end_if_label.s()));
+                then.emit_arm32(b)?;
+                match c_else {
+                    Some(c_else) => {
+                        let end_if_label = Label::n();
+                        b.add(&format!("  b {}", end_if_label.s()));
+                        b.add(&format!("{}:", if_false_label.s()));
+                        c_else.emit_arm32(b)?;
+                        b.add(&format!("{}:", end_if_label.s()));
+                    }
+                    // without an else, false jumps straight past the then
+                    None => b.add(&format!("{}:", if_false_label.s())),
+                }
             }
             AST::FunctionDef {
-                span: _,
+                span,
                 name,
                 params,
+                types,
+                ret,
                 body,
             } => {
-                if params.len() > 4 {
-                    panic!("sac doesn't support more than 4 parameters :(");
-                }
                 b.add("");
                 b.add(&format!(".global {name}"));
                 b.add(&format!("{name}:"));
@@ -452,90 +1487,444 @@ impl AST {
                 b.add("  push {fp, lr}");
                 b.add("  mov fp, sp");
 
-                // push the right number of registers onto the stack
-                // that will serve as parameters in the function body
-                match params.len() {
-                    1 => {
-                        b.add("  push {r0}"); // use fp to pad to 8 bytes
-                    }
-                    2 => {
-                        b.add("  push {r0, r1}");
-                    }
-                    3 => {
-                        b.add("  push {r0, r1, r2}"); // use fp to pad to 8 bytes
-                    }
-                    4 => {
-                        b.add("  push {r0, r1, r2, r3}");
-                    }
-                    _ => {}
+                // push the parameters that came in registers onto the
+                // stack, where the function body reads them from
+                let types: Vec<_> = types.iter().map(annotated).collect();
+                let (_, in_regs, _) = arg_slots(&types);
+                if in_regs > 0 {
+                    b.add(&format!("  push {{{}}}", ARG_REGS[in_regs - 1]));
                 }
 
                 // body codegen
-                b.enter_ctx();
-                b.set_up_env(params);
-                body.emit_arm32(b);
-                b.exit_ctx();
+                b.enter_frame(params, &types, annotated(ret));
+                body.emit_arm32(b)?;
+                b.exit_frame()
+                    .map_err(|msg| Diagnostic::error(span.clone(), msg))?;
 
-                // function epilogue
+                // function epilogue. falling off the end returns 0, all 64
+                // bits of it for an i64
                 b.add("  mov sp, fp");
                 b.add("  mov r0, #0");
+                if annotated(ret) == Type::I64 {
+                    b.add("  mov r1, #0");
+                }
                 b.add("  pop {fp, pc}");
             }
-            AST::Identifier { name, span: _ } => {
-                if let Some(offset) = b.try_get(name) {
-                    b.add(&format!("  ldr r0, [fp, #{offset}]"));
+            AST::Identifier { name, span } => {
+                // locals shadow globals and constants
+                if let Some(local) = b.try_get(name).cloned() {
+                    load_frame(b, &local.ty, local.offset);
+                } else if let Some(label) = b.try_get_global(name) {
+                    b.add(&format!("  ldr r1, ={}", label.s()));
+                    b.add("  ldr r0, [r1]");
+                } else if let Some(value) = b.try_get_const(name) {
+                    b.add(&format!("  ldr r0, ={value}"));
                 } else {
-                    panic!("undefined variable: `{name}` :(");
+                    return Err(undefined_variable(name, span));
                 }
             }
             AST::Return { value, span: _ } => {
-                value.emit_arm32(b);
+                emit_as(value, &b.returns(), b)?;
                 b.add("  mov sp, fp");
                 b.add("  pop {fp, pc}");
             }
-            AST::StrLiteral { literal } => {
+            AST::StrLiteral { literal, span: _ } => {
                 // intern string and get label to assign to r0
                 let label = b.add_interned_str(literal.clone());
                 b.add(&format!("  ldr r0, ={}", label.s()));
             }
-            AST::Variable {
-                span: _,
+            AST::Array { span, .. } => {
+                return Err(Diagnostic::error(
+                    span.clone(),
+                    "an array can only be the value of a `mut`".into(),
+                ));
+            }
+            AST::StructLit { span, .. } => {
+                return Err(Diagnostic::error(
+                    span.clone(),
+                    "a struct can only be the value of a `mut`".into(),
+                ));
+            }
+            AST::Field { span, name, field } => {
+                let offset = field_offset(b, name, field, span)?;
+                let operand = frame_operand(b, "ldr", offset);
+                b.add(&format!("  ldr r0, {operand}"));
+            }
+            AST::FieldAssign {
+                span,
                 name,
+                field,
                 value,
             } => {
-                value.emit_arm32(b);
-                b.add("  push {r0}");
-                b.set(name.clone());
+                let offset = field_offset(b, name, field, span)?;
+                value.emit_arm32(b)?;
+                let operand = frame_operand(b, "str", offset);
+                b.add(&format!("  str r0, {operand}"));
             }
-            AST::Assignment {
-                span: _,
+            AST::Index { span, name, index } => {
+                let (offset, of, len) = array_local(b, name, span)?;
+                index.emit_arm32(b)?;
+                bounds_check(b, "r0", len);
+                b.add(&format!("  ldr r1, ={offset}"));
+                b.add("  add r1, fp, r1");
+                let element = element(b, "r1", "r0", &of);
+                b.add(&format!("  {} r0, {element}", of.load()));
+                if of == Type::I64 {
+                    b.add("  ldr r1, [r1, #4]");
+                }
+            }
+            AST::IndexAssign {
+                span,
                 name,
+                index,
                 value,
             } => {
-                if let Some(&offset) = b.try_get(name) {
-                    value.emit_arm32(b);
-                    b.add(&format!("  str r0, [fp, #{offset}]"));
+                let (offset, of, len) = array_local(b, name, span)?;
+                index.emit_arm32(b)?;
+                b.add("  push {r0, ip}");
+                emit_as(value, &of, b)?;
+                if of == Type::I64 {
+                    // r1 holds the high word, the index goes in ip and the
+                    // element's address in r3
+                    b.add("  pop {r2, ip}");
+                    b.add("  mov ip, r2");
+                    bounds_check(b, "ip", len);
+                    b.add(&format!("  ldr r3, ={offset}"));
+                    b.add("  add r3, fp, r3");
+                    let element = element(b, "r3", "ip", &of);
+                    b.add(&format!("  str r0, {element}"));
+                    b.add("  str r1, [r3, #4]");
+                    return Ok(());
+                }
+                b.add("  pop {r1, ip}");
+                bounds_check(b, "r1", len);
+                b.add(&format!("  ldr r2, ={offset}"));
+                b.add("  add r2, fp, r2");
+                let element = element(b, "r2", "r1", &of);
+                b.add(&format!("  {} r0, {element}", of.store()));
+            }
+            AST::Variable {
+                span, name, value, ..
+            } => {
+                if let AST::Array { value, len, .. } = value.as_ref() {
+                    let len = len
+                        .fold(&b.consts())
+                        .filter(|&len| len > 0)
+                        .ok_or_else(|| Diagnostic::error(span.clone(), bad_length(name)))?;
+                    let of = match b.type_of(self) {
+                        Type::Array(of, _) => *of,
+                        _ => Type::Any,
+                    };
+                    emit_as(value, &of, b)?;
+                    if of == Type::I64 {
+                        b.add("  mov r3, r1");
+                    }
+                    let offset = b.set(name.clone(), Type::Array(Box::new(of.clone()), len));
+                    // every element starts as a copy of the value
+                    let fill = Label::n();
+                    b.add(&format!("  ldr r1, ={offset}"));
+                    b.add("  add r1, fp, r1");
+                    b.add(&format!("  ldr r2, ={len}"));
+                    b.add(&format!("{}:", fill.s()));
+                    match of {
+                        Type::I64 => {
+                            b.add("  str r0, [r1], #4");
+                            b.add("  str r3, [r1], #4");
+                        }
+                        _ => b.add(&format!("  {} r0, [r1], #{}", of.store(), of.size())),
+                    }
+                    b.add("  subs r2, r2, #1");
+                    b.add(&format!("  bne {}", fill.s()));
+                } else if let AST::StructLit {
+                    name: layout,
+                    fields,
+                    ..
+                } = value.as_ref()
+                {
+                    // the fields are generated before `name` is in scope, as
+                    // for any other value
+                    for (_, value) in fields {
+                        value.emit_arm32(b)?;
+                        b.add("  push {r0, ip}");
+                    }
+                    let offset = b.set_struct(name.clone(), layout).ok_or_else(|| {
+                        Diagnostic::error(span.clone(), format!("cannot find struct `{layout}`"))
+                    })?;
+                    for (field, _) in fields.iter().rev() {
+                        let at = b
+                            .try_get_struct(layout)
+                            .and_then(|layout| layout.offset(field))
+                            .ok_or_else(|| no_field(layout, field, span))?;
+                        b.add("  pop {r0, ip}");
+                        let operand = frame_operand(b, "str", offset + at);
+                        b.add(&format!("  str r0, {operand}"));
+                    }
+                } else {
+                    // the value is generated first, so `mut x = x + 1` in an
+                    // inner block reads the outer `x`
+                    let ty = b.type_of(self);
+                    emit_as(value, &ty, b)?;
+                    let offset = b.set(name.clone(), ty.clone());
+                    store_frame(b, &ty, offset);
+                }
+            }
+            AST::Assignment { span, name, value } => {
+                if let Some(local) = b.try_get(name).cloned() {
+                    emit_as(value, &local.ty, b)?;
+                    store_frame(b, &local.ty, local.offset);
+                } else if let Some(label) = b.try_get_global(name) {
+                    value.emit_arm32(b)?;
+                    b.add(&format!("  ldr r1, ={}", label.s()));
+                    b.add("  str r0, [r1]");
                 } else {
-                    panic!("undefined variable: `{name}` :(");
+                    return Err(undefined_variable(name, span));
                 }
             }
+            // already laid out by declare_globals, and C functions are
+            // found by the linker
+            AST::Global { .. } | AST::Const { .. } | AST::StructDef { .. } | AST::Extern { .. } => {
+            }
             AST::WhileLoop {
                 span: _,
                 condition,
                 body,
+                step,
             } => {
                 let loop_start = Label::n();
                 let loop_end = Label::n();
+                // `continue` has to run the step before checking again
+                let loop_next = match step {
+                    Some(_) => Label::n(),
+                    None => loop_start.clone(),
+                };
 
                 b.add(&format!("{}:", loop_start.s()));
-                condition.emit_arm32(b);
+                condition.emit_arm32(b)?;
                 b.add("  cmp r0, #0");
                 b.add(&format!("  beq {}", loop_end.s()));
-                body.emit_arm32(b);
+                b.enter_loop(loop_next.clone(), loop_end.clone());
+                body.emit_arm32(b)?;
+                b.exit_loop();
+                if let Some(step) = step {
+                    b.add(&format!("{}:", loop_next.s()));
+                    step.emit_arm32(b)?;
+                }
                 b.add(&format!("  b {}", loop_start.s()));
                 b.add(&format!("{}:", loop_end.s()));
             }
-            _ => panic!("unimplemented :("),
+            AST::Break { span } | AST::Continue { span } => {
+                let Some((loop_next, loop_end)) = b.current_loop() else {
+                    return Err(outside_loop(self, span));
+                };
+                let target = match self {
+                    AST::Break { .. } => loop_end,
+                    _ => loop_next,
+                };
+                b.add(&format!("  b {}", target.s()));
+            }
+            AST::Error { span, msg } => return Err(Diagnostic::error(span.clone(), msg.clone())),
         }
+        Ok(())
+    }
+}
+
+// `node` is a Break or a Continue
+pub fn outside_loop(node: &AST, span: &Span) -> Diagnostic {
+    let word = match node {
+        AST::Break { .. } => "break",
+        _ => "continue",
+    };
+    Diagnostic::error(span.clone(), format!("`{word}` outside of a loop"))
+        .note(format!("`{word}` can only be used inside a loop body"))
+}
+
+pub fn undefined_variable(name: &str, span: &Span) -> Diagnostic {
+    Diagnostic::error(span.clone(), format!("undefined variable `{name}`")).help(format!(
+        "declare it with `mut {name} = ...;` before using it"
+    ))
+}
+
+pub fn not_constant(name: &str, span: &Span) -> Diagnostic {
+    Diagnostic::error(
+        span.clone(),
+        format!("the value of `{name}` must be known at compile time"),
+    )
+    .note("only numbers, constants and operators on them can be used here".into())
+}
+
+pub fn temporary_address(span: &Span) -> Diagnostic {
+    Diagnostic::error(
+        span.clone(),
+        "cannot take the address of a temporary value".into(),
+    )
+    .note("only variables, array elements and dereferences have an address".into())
+}
+
+pub fn bad_length(name: &str) -> String {
+    format!("the length of `{name}` must be a constant above 0")
+}
+
+// where array `name` starts in the frame, the type of its elements and how
+// many there are
+fn array_local(b: &Builder, name: &str, span: &Span) -> Result<(i32, Type, i32), Diagnostic> {
+    match b.try_get(&name.to_string()) {
+        Some(Local {
+            offset,
+            ty: Type::Array(of, len),
+            ..
+        }) => Ok((*offset, *of.clone(), *len)),
+        Some(local) => Ok((local.offset, Type::Any, local.words)),
+        None => Err(undefined_variable(name, span)),
+    }
+}
+
+// the operand addressing element `index` of the array at `base`, both
+// registers. ldrh and strh cannot shift the index, and i64s are read a word
+// at a time, so for them it is added to `base` first
+fn element(b: &mut Builder, base: &str, index: &str, of: &Type) -> String {
+    match of.size() {
+        1 => format!("[{base}, {index}]"),
+        2 => {
+            b.add(&format!("  add {base}, {base}, {index}, lsl #1"));
+            format!("[{base}]")
+        }
+        8 => {
+            b.add(&format!("  add {base}, {base}, {index}, lsl #3"));
+            format!("[{base}]")
+        }
+        _ => format!("[{base}, {index}, lsl #2]"),
+    }
+}
+
+// the operand for `op` on the frame slot at `offset`. ldr, str, ldrb and
+// strb reach 4095 bytes from fp, the halfword and signed byte forms only
+// 255, so further slots go through ip
+fn frame_operand(b: &mut Builder, op: &str, offset: i32) -> String {
+    let reach = match op {
+        "ldr" | "str" | "ldrb" | "strb" => 4095,
+        _ => 255,
+    };
+    if (-reach..=reach).contains(&offset) {
+        format!("[fp, #{offset}]")
+    } else {
+        b.add(&format!("  ldr ip, ={offset}"));
+        "[fp, ip]".into()
+    }
+}
+
+// reads the local of type `ty` at `offset` into r0, or r0:r1 for an i64
+fn load_frame(b: &mut Builder, ty: &Type, offset: i32) {
+    let operand = frame_operand(b, ty.load(), offset);
+    b.add(&format!("  {} r0, {operand}", ty.load()));
+    if ty == &Type::I64 {
+        let operand = frame_operand(b, "ldr", offset + 4);
+        b.add(&format!("  ldr r1, {operand}"));
+    }
+}
+
+// writes r0, or r0:r1 for an i64, to the local of type `ty` at `offset`
+fn store_frame(b: &mut Builder, ty: &Type, offset: i32) {
+    let operand = frame_operand(b, ty.store(), offset);
+    b.add(&format!("  {} r0, {operand}", ty.store()));
+    if ty == &Type::I64 {
+        let operand = frame_operand(b, "str", offset + 4);
+        b.add(&format!("  str r1, {operand}"));
+    }
+}
+
+// the type codegen works with for a missing annotation
+fn annotated(ty: &Option<Type>) -> Type {
+    ty.clone().unwrap_or(Type::Any)
+}
+
+// whether `ast` is an i64, held in r0:r1
+fn wide(b: &Builder, ast: &AST) -> bool {
+    b.type_of(ast) == Type::I64
+}
+
+// whether `ast` is a u32, which divides, shifts and compares unsigned
+fn unsigned(b: &Builder, ast: &AST) -> bool {
+    b.type_of(ast) == Type::U32
+}
+
+// the conditions for `lhs` against `rhs` being true and false, as written
+// for signed operands. a u32 on either side turns them into their unsigned
+// counterparts
+fn conditions(
+    b: &Builder,
+    lhs: &AST,
+    rhs: &AST,
+    t: &'static str,
+    f: &'static str,
+) -> (&'static str, &'static str) {
+    let unsigned_of = |cond| match cond {
+        "lt" => "lo",
+        "gt" => "hi",
+        "le" => "ls",
+        _ => "hs",
+    };
+    match unsigned(b, lhs) || unsigned(b, rhs) {
+        true => (unsigned_of(t), unsigned_of(f)),
+        false => (t, f),
+    }
+}
+
+// generates `ast` as an i64: narrower values are extended into r1, by their
+// sign unless they are unsigned
+fn emit_wide(ast: &AST, b: &mut Builder) -> Result<(), Diagnostic> {
+    ast.emit_arm32(b)?;
+    match b.type_of(ast) {
+        Type::I64 => {}
+        Type::U32 => b.add("  mov r1, #0"),
+        _ => b.add("  asr r1, r0, #31"),
+    }
+    Ok(())
+}
+
+// generates `value` to be stored as a `ty`
+fn emit_as(value: &AST, ty: &Type, b: &mut Builder) -> Result<(), Diagnostic> {
+    match ty {
+        Type::I64 => emit_wide(value, b),
+        _ => value.emit_arm32(b),
+    }
+}
+
+// both operands of a 64-bit operation, lhs in r2:r3 and rhs in r0:r1
+fn wide_operands(b: &mut Builder, lhs: &AST, rhs: &AST) -> Result<(), Diagnostic> {
+    emit_wide(lhs, b)?;
+    b.add("  push {r0, r1}");
+    emit_wide(rhs, b)?;
+    b.add("  pop {r2, r3}");
+    Ok(())
+}
+
+pub fn no_field(layout: &str, field: &str, span: &Span) -> Diagnostic {
+    Diagnostic::error(span.clone(), format!("`{layout}` has no field `{field}`"))
+}
+
+// where `name.field` is in the frame
+fn field_offset(b: &Builder, name: &str, field: &str, span: &Span) -> Result<i32, Diagnostic> {
+    let local = b
+        .try_get(&name.to_string())
+        .ok_or_else(|| undefined_variable(name, span))?;
+    let layout = local
+        .layout
+        .as_deref()
+        .ok_or_else(|| Diagnostic::error(span.clone(), format!("`{name}` is not a struct")))?;
+    b.try_get_struct(layout)
+        .and_then(|found| found.offset(field))
+        .map(|at| local.offset + at)
+        .ok_or_else(|| no_field(layout, field, span))
+}
+
+// with bounds checks on, traps unless 0 <= `reg` < `len`. the unsigned
+// comparison catches negative indexes too
+fn bounds_check(b: &mut Builder, reg: &str, len: i32) {
+    if !b.checks_bounds() {
+        return;
     }
+    let trap = b.trap("OutOfBounds", "array index out of bounds");
+    b.add(&format!("  ldr r2, ={len}"));
+    b.add(&format!("  cmp {reg}, r2"));
+    b.add(&format!("  bhs {}", trap.s()));
 }
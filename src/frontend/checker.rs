@@ -0,0 +1,906 @@
+use std::{collections::HashMap, mem};
+
+use super::ast::{
+    bad_length, not_constant, outside_loop, temporary_address, undefined_variable, AST,
+};
+use super::diagnostics::Diagnostic;
+use super::span::Span;
+
+// C library functions sac programs may call without an `extern`, the ones
+// for printing, reading input, memory and exiting. any other has to be
+// declared with `extern name;`
+const LIBC_FUNCTIONS: &[&str] = &[
+    "printf", "puts", "putchar", "getchar", "scanf", "malloc", "free", "exit", "abs", "rand",
+    "srand", "time",
+];
+
+struct Function {
+    params: usize,
+    span: Span,
+}
+
+struct Struct {
+    fields: Vec<String>,
+    span: Span,
+}
+
+// a parameter or `mut` in view, with the depth of the block that declared
+// it, the length of arrays and the struct of struct instances
+#[derive(Clone)]
+struct Local {
+    span: Span,
+    depth: usize,
+    len: Option<i32>,
+    layout: Option<String>,
+}
+
+// resolves every name in a parsed file before any code is emitted. it finds
+// undefined variables and functions, calls with the wrong number of
+// arguments, names defined twice, variables used before their `mut` and
+// `break`/`continue` outside of loops
+struct Checker {
+    functions: HashMap<String, Function>,
+    // top level `global`s and `const`s, and the folded values of the consts
+    globals: HashMap<String, Span>,
+    consts: HashMap<String, i32>,
+    // the C functions declared with `extern`
+    externs: Vec<String>,
+    structs: HashMap<String, Struct>,
+    in_function: bool,
+    // locals of the function being checked that are in view so far
+    locals: HashMap<String, Local>,
+    // how many blocks the node being checked is inside of
+    block_depth: usize,
+    // every `mut` in the function being checked, wherever it is
+    declarations: HashMap<String, Span>,
+    // how many `while`s the node being checked is inside of
+    loop_depth: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+// checks a whole file, returning everything wrong with it
+pub fn check(ast: &AST) -> Vec<Diagnostic> {
+    let mut checker = Checker {
+        functions: HashMap::new(),
+        globals: HashMap::new(),
+        consts: HashMap::new(),
+        externs: vec![],
+        structs: HashMap::new(),
+        in_function: false,
+        locals: HashMap::new(),
+        block_depth: 0,
+        declarations: declarations(ast),
+        loop_depth: 0,
+        diagnostics: vec![],
+    };
+    // functions can be called before they are defined, so find them all first
+    checker.collect_functions(ast);
+    checker.collect_globals(ast);
+    checker.check(ast);
+    checker.diagnostics
+}
+
+// the first `mut` of each name in `ast`, leaving out nested functions
+fn declarations(ast: &AST) -> HashMap<String, Span> {
+    fn walk(ast: &AST, found: &mut HashMap<String, Span>) {
+        match ast {
+            AST::FunctionDef { .. } => {}
+            AST::Variable {
+                span, name, value, ..
+            } => {
+                walk(value, found);
+                found.entry(name.clone()).or_insert(span.clone());
+            }
+            _ => {
+                for child in ast.children() {
+                    walk(child, found);
+                }
+            }
+        }
+    }
+
+    let mut found = HashMap::new();
+    for child in ast.children() {
+        walk(child, &mut found);
+    }
+    found
+}
+
+fn plural(count: usize, word: &str) -> String {
+    if count == 1 {
+        format!("{count} {word}")
+    } else {
+        format!("{count} {word}s")
+    }
+}
+
+impl Checker {
+    fn collect_functions(&mut self, ast: &AST) {
+        if let AST::FunctionDef {
+            span, name, params, ..
+        } = ast
+        {
+            if let Some(first) = self.functions.get(name) {
+                self.diagnostics.push(
+                    Diagnostic::error(
+                        span.clone(),
+                        format!("function `{name}` is defined more than once"),
+                    )
+                    .note(format!("first defined at {}", first.span)),
+                );
+            } else {
+                self.functions.insert(
+                    name.clone(),
+                    Function {
+                        params: params.len(),
+                        span: span.clone(),
+                    },
+                );
+            }
+        }
+        for child in ast.children() {
+            self.collect_functions(child);
+        }
+    }
+
+    // the top level globals, constants and structs. globals and constants
+    // are in order, a constant can only use the ones before it
+    fn collect_globals(&mut self, ast: &AST) {
+        for stmt in ast.children() {
+            let (span, name, value) = match stmt {
+                AST::Global { span, name, value } | AST::Const { span, name, value } => {
+                    (span, name, value)
+                }
+                AST::StructDef { span, name, fields } => {
+                    self.collect_struct(span, name, fields);
+                    continue;
+                }
+                AST::Extern { name, .. } => {
+                    self.externs.push(name.clone());
+                    continue;
+                }
+                _ => continue,
+            };
+            if let Some(first) = self.globals.get(name) {
+                self.diagnostics.push(
+                    Diagnostic::error(span.clone(), format!("`{name}` is defined more than once"))
+                        .note(format!("first defined at {first}")),
+                );
+                continue;
+            }
+            self.globals.insert(name.clone(), span.clone());
+            match value.fold(&self.consts) {
+                Some(value) if matches!(stmt, AST::Const { .. }) => {
+                    self.consts.insert(name.clone(), value);
+                }
+                Some(_) => {}
+                None => self.diagnostics.push(not_constant(name, span)),
+            }
+        }
+    }
+
+    fn collect_struct(&mut self, span: &Span, name: &str, fields: &[String]) {
+        if let Some(first) = self.structs.get(name) {
+            self.diagnostics.push(
+                Diagnostic::error(
+                    span.clone(),
+                    format!("struct `{name}` is defined more than once"),
+                )
+                .note(format!("first defined at {}", first.span)),
+            );
+            return;
+        }
+        for (i, field) in fields.iter().enumerate() {
+            if fields[..i].contains(field) {
+                self.diagnostics.push(Diagnostic::error(
+                    span.clone(),
+                    format!("field `{field}` is listed more than once"),
+                ));
+            }
+        }
+        let found = Struct {
+            fields: fields.to_vec(),
+            span: span.clone(),
+        };
+        self.structs.insert(name.into(), found);
+    }
+
+    fn check(&mut self, ast: &AST) {
+        match ast {
+            AST::FunctionDef {
+                span, params, body, ..
+            } => {
+                let outer_locals = mem::take(&mut self.locals);
+                let outer_block_depth = mem::take(&mut self.block_depth);
+                let outer_in_function = mem::replace(&mut self.in_function, true);
+                let outer_loop_depth = mem::take(&mut self.loop_depth);
+                let outer_declarations = mem::replace(&mut self.declarations, declarations(body));
+                for param in params {
+                    // parameters are in the same scope as the top of the
+                    // body, which cannot redeclare them
+                    if self.locals.contains_key(param) {
+                        self.diagnostics.push(Diagnostic::error(
+                            span.clone(),
+                            format!("parameter `{param}` is listed more than once"),
+                        ));
+                    }
+                    self.locals.insert(
+                        param.clone(),
+                        Local {
+                            span: span.clone(),
+                            depth: 1,
+                            len: None,
+                            layout: None,
+                        },
+                    );
+                }
+                self.check(body);
+                self.locals = outer_locals;
+                self.block_depth = outer_block_depth;
+                self.in_function = outer_in_function;
+                self.loop_depth = outer_loop_depth;
+                self.declarations = outer_declarations;
+            }
+            AST::Variable { span, name, .. } if !self.in_function => {
+                self.diagnostics.push(
+                    Diagnostic::error(span.clone(), "`mut` outside of a function".into())
+                        .help(format!("use `global {name} = ...;` for a global variable")),
+                );
+            }
+            AST::Global { span, .. }
+            | AST::Const { span, .. }
+            | AST::StructDef { span, .. }
+            | AST::Extern { span, .. }
+                if self.in_function =>
+            {
+                let word = match ast {
+                    AST::Global { .. } => "global",
+                    AST::Const { .. } => "const",
+                    AST::Extern { .. } => "extern",
+                    _ => "struct",
+                };
+                self.diagnostics.push(
+                    Diagnostic::error(span.clone(), format!("`{word}` inside of a function"))
+                        .note(format!("`{word}` can only be used at the top level")),
+                );
+            }
+            // collect_globals has checked them
+            AST::Global { .. } | AST::Const { .. } | AST::StructDef { .. } | AST::Extern { .. } => {
+            }
+            AST::Variable {
+                span, name, value, ..
+            } => {
+                // the value is checked first: `mut x = x;` uses x before it exists
+                let mut layout = None;
+                let len = match value.as_ref() {
+                    AST::StructLit {
+                        name: found,
+                        fields,
+                        span,
+                    } => {
+                        for (_, value) in fields {
+                            self.check(value);
+                        }
+                        if self.check_struct_lit(found, fields, span) {
+                            layout = Some(found.clone());
+                        }
+                        None
+                    }
+                    AST::Array { value, len, .. } => {
+                        self.check(value);
+                        let len = len.fold(&self.consts()).filter(|&len| len > 0);
+                        if len.is_none() {
+                            self.diagnostics
+                                .push(Diagnostic::error(span.clone(), bad_length(name)));
+                        }
+                        // still an array, so its uses are checked as one
+                        Some(len.unwrap_or(1))
+                    }
+                    _ => {
+                        self.check(value);
+                        None
+                    }
+                };
+                // an inner block may shadow a name, its own scope may not
+                // declare it twice
+                match self.locals.get(name) {
+                    Some(first) if first.depth == self.block_depth => self.diagnostics.push(
+                        Diagnostic::error(span.clone(), format!("`{name}` is already declared"))
+                            .note(format!("first declared at {}", first.span))
+                            .help(format!("use `{name} = ...;` to give it a new value")),
+                    ),
+                    _ => {
+                        let local = Local {
+                            span: span.clone(),
+                            depth: self.block_depth,
+                            len,
+                            layout,
+                        };
+                        self.locals.insert(name.clone(), local);
+                    }
+                }
+            }
+            AST::Block { statements, .. } => {
+                // what a block declares is gone once it ends
+                let outer_locals = self.locals.clone();
+                self.block_depth += 1;
+                for stmt in statements {
+                    self.check(stmt);
+                }
+                self.block_depth -= 1;
+                self.locals = outer_locals;
+            }
+            AST::WhileLoop {
+                condition,
+                body,
+                step,
+                ..
+            } => {
+                self.check(condition);
+                self.loop_depth += 1;
+                self.check(body);
+                self.loop_depth -= 1;
+                if let Some(step) = step {
+                    self.check(step);
+                }
+            }
+            AST::Break { span } | AST::Continue { span } => {
+                if self.loop_depth == 0 {
+                    self.diagnostics.push(outside_loop(ast, span));
+                }
+            }
+            AST::Array { span, value, .. } => {
+                self.diagnostics.push(
+                    Diagnostic::error(
+                        span.clone(),
+                        "an array can only be the value of a `mut`".into(),
+                    )
+                    .help("declare it with `mut buf = [0; 16];`".into()),
+                );
+                self.check(value);
+            }
+            AST::StructLit { span, fields, .. } => {
+                self.diagnostics.push(
+                    Diagnostic::error(
+                        span.clone(),
+                        "a struct can only be the value of a `mut`".into(),
+                    )
+                    .help("declare it with `mut p = Point { x: 1, y: 2 };`".into()),
+                );
+                for (_, value) in fields {
+                    self.check(value);
+                }
+            }
+            AST::Field { span, name, field } => self.check_field(name, field, span),
+            AST::FieldAssign {
+                span,
+                name,
+                field,
+                value,
+            } => {
+                self.check(value);
+                self.check_field(name, field, span);
+            }
+            AST::Index { span, name, index } => {
+                self.check(index);
+                self.check_index(name, index, span);
+            }
+            AST::IndexAssign {
+                span,
+                name,
+                index,
+                value,
+            } => {
+                self.check(index);
+                self.check(value);
+                self.check_index(name, index, span);
+            }
+            AST::AddressOf { target, span } => match target.as_ref() {
+                // an array's address is that of its element 0, as in C
+                AST::Identifier { name, .. } if self.is_array(name) || self.is_struct(name) => {}
+                AST::Identifier { name, .. }
+                    if !self.locals.contains_key(name) && self.consts.contains_key(name) =>
+                {
+                    self.diagnostics.push(
+                        Diagnostic::error(
+                            span.clone(),
+                            format!("cannot take the address of constant `{name}`"),
+                        )
+                        .note("constants are folded into the code, they have no address".into()),
+                    );
+                }
+                AST::Identifier { .. }
+                | AST::Index { .. }
+                | AST::Field { .. }
+                | AST::Deref { .. } => self.check(target),
+                _ => {
+                    self.diagnostics.push(temporary_address(span));
+                    self.check(target);
+                }
+            },
+            AST::Identifier { name, span } if self.is_array(name) => {
+                self.diagnostics.push(
+                    Diagnostic::error(span.clone(), format!("`{name}` is an array"))
+                        .help(format!("read an element with `{name}[i]`")),
+                );
+            }
+            AST::Identifier { name, span } if self.is_struct(name) => {
+                self.diagnostics.push(
+                    Diagnostic::error(span.clone(), format!("`{name}` is a struct"))
+                        .help(format!("read a field with `{name}.field`")),
+                );
+            }
+            AST::Identifier { name, span } => self.resolve(name, span),
+            AST::Assignment { span, name, value } if self.is_array(name) => {
+                self.check(value);
+                self.diagnostics.push(
+                    Diagnostic::error(span.clone(), format!("`{name}` is an array"))
+                        .help(format!("write an element with `{name}[i] = ...;`")),
+                );
+            }
+            AST::Assignment { span, name, value } if self.is_struct(name) => {
+                self.check(value);
+                self.diagnostics.push(
+                    Diagnostic::error(span.clone(), format!("`{name}` is a struct"))
+                        .help(format!("write a field with `{name}.field = ...;`")),
+                );
+            }
+            AST::Assignment { span, name, value } => {
+                self.check(value);
+                if !self.locals.contains_key(name) && self.consts.contains_key(name) {
+                    self.diagnostics.push(
+                        Diagnostic::error(
+                            span.clone(),
+                            format!("cannot assign to constant `{name}`"),
+                        )
+                        .note(format!("`{name}` is declared at {}", self.globals[name])),
+                    );
+                } else {
+                    self.resolve(name, span);
+                }
+            }
+            AST::Call { called, args, span } => {
+                for arg in args {
+                    self.check(arg);
+                }
+                match self.functions.get(called) {
+                    Some(func) if func.params != args.len() => self.diagnostics.push(
+                        Diagnostic::error(
+                            span.clone(),
+                            format!(
+                                "`{called}` takes {} but {} supplied",
+                                plural(func.params, "argument"),
+                                match args.len() {
+                                    1 => "1 was".to_string(),
+                                    len => format!("{len} were"),
+                                }
+                            ),
+                        )
+                        .note(format!("`{called}` is defined at {}", func.span)),
+                    ),
+                    Some(_) => {}
+                    // C functions are left to the linker, their arguments
+                    // are not checked
+                    None if LIBC_FUNCTIONS.contains(&called.as_str())
+                        || self.externs.contains(called) => {}
+                    None => self.diagnostics.push(
+                        Diagnostic::error(span.clone(), format!("cannot find function `{called}`"))
+                            .help(format!("define it with `:{called} ... {{ }}`"))
+                            .help(format!(
+                                "or, for a C function, declare it with `extern {called};`"
+                            )),
+                    ),
+                }
+            }
+            _ => {
+                for child in ast.children() {
+                    self.check(child);
+                }
+            }
+        }
+    }
+
+    // the constants not shadowed by a local in view
+    fn consts(&self) -> HashMap<String, i32> {
+        let mut consts = self.consts.clone();
+        consts.retain(|name, _| !self.locals.contains_key(name));
+        consts
+    }
+
+    fn is_struct(&self, name: &str) -> bool {
+        matches!(
+            self.locals.get(name),
+            Some(Local {
+                layout: Some(_),
+                ..
+            })
+        )
+    }
+
+    // `name { fields }` has to give every field of struct `name` once. false
+    // if there is no such struct
+    fn check_struct_lit(&mut self, name: &str, fields: &[(String, AST)], span: &Span) -> bool {
+        let Some(found) = self.structs.get(name) else {
+            self.diagnostics.push(
+                Diagnostic::error(span.clone(), format!("cannot find struct `{name}`"))
+                    .help(format!("declare it with `struct {name} {{ ... }}`")),
+            );
+            return false;
+        };
+        let mut diagnostics = vec![];
+        for (i, (field, value)) in fields.iter().enumerate() {
+            if !found.fields.contains(field) {
+                diagnostics.push(
+                    Diagnostic::error(value.get_span(), format!("`{name}` has no field `{field}`"))
+                        .note(format!("`{name}` is defined at {}", found.span)),
+                );
+            } else if fields[..i].iter().any(|(given, _)| given == field) {
+                diagnostics.push(Diagnostic::error(
+                    value.get_span(),
+                    format!("field `{field}` is given more than once"),
+                ));
+            }
+        }
+        for field in &found.fields {
+            if !fields.iter().any(|(given, _)| given == field) {
+                diagnostics.push(Diagnostic::error(
+                    span.clone(),
+                    format!("missing field `{field}` in `{name}`"),
+                ));
+            }
+        }
+        self.diagnostics.extend(diagnostics);
+        true
+    }
+
+    // `name.field` needs `name` to be a struct with that field
+    fn check_field(&mut self, name: &str, field: &str, span: &Span) {
+        let layout = match self.locals.get(name) {
+            Some(Local {
+                layout: Some(layout),
+                ..
+            }) => layout,
+            None if !self.globals.contains_key(name) => return self.resolve(name, span),
+            _ => {
+                self.diagnostics.push(Diagnostic::error(
+                    span.clone(),
+                    format!("`{name}` is not a struct"),
+                ));
+                return;
+            }
+        };
+        let found = &self.structs[layout];
+        if !found.fields.iter().any(|known| known == field) {
+            let diag =
+                Diagnostic::error(span.clone(), format!("`{layout}` has no field `{field}`"))
+                    .note(format!("`{layout}` is defined at {}", found.span));
+            self.diagnostics.push(diag);
+        }
+    }
+
+    fn is_array(&self, name: &str) -> bool {
+        matches!(self.locals.get(name), Some(Local { len: Some(_), .. }))
+    }
+
+    // `name[index]` needs `name` to be an array, and a constant `index` to be
+    // inside it
+    fn check_index(&mut self, name: &str, index: &AST, span: &Span) {
+        let len = match self.locals.get(name) {
+            Some(Local { len: Some(len), .. }) => *len,
+            None if !self.globals.contains_key(name) => return self.resolve(name, span),
+            _ => {
+                self.diagnostics.push(Diagnostic::error(
+                    span.clone(),
+                    format!("`{name}` is not an array"),
+                ));
+                return;
+            }
+        };
+        match index.fold(&self.consts()) {
+            Some(at) if at < 0 || at >= len => self.diagnostics.push(
+                Diagnostic::error(
+                    index.get_span(),
+                    format!("index {at} is out of bounds for `{name}`"),
+                )
+                .note(format!("`{name}` has {}", plural(len as usize, "element"))),
+            ),
+            _ => {}
+        }
+    }
+
+    fn resolve(&mut self, name: &str, span: &Span) {
+        if self.locals.contains_key(name) || self.globals.contains_key(name) {
+            return;
+        }
+        let diag = match self.declarations.get(name) {
+            Some(declared)
+                if (declared.end_line, declared.end_col) <= (span.start_line, span.start_col) =>
+            {
+                Diagnostic::error(span.clone(), format!("`{name}` is not in scope here")).note(
+                    format!("it is declared at {declared}, in a block that has ended"),
+                )
+            }
+            Some(declared) => Diagnostic::error(
+                span.clone(),
+                format!("`{name}` is used before its declaration"),
+            )
+            .note(format!("it is declared at {declared}")),
+            None => undefined_variable(name, span),
+        };
+        self.diagnostics.push(diag);
+    }
+}
+
+// the messages `check` produces for `src`, with the line each one is on
+#[cfg(test)]
+fn check_src(src: &str) -> Vec<(i32, String)> {
+    use super::{sac_parser::sac_parser, source::Source};
+
+    let (_, ast, _) = sac_parser(Source::from(src)).unwrap();
+    assert!(ast.errors().is_empty());
+    check(&ast)
+        .into_iter()
+        .map(|diag| (diag.span.start_line, diag.msg))
+        .collect()
+}
+
+#[test]
+fn test_check() {
+    let src = "\
+:main {
+  printf(\"%d\\n\", fact(5));
+  ret 0;
+}
+:fact n {
+  mut res = 1;
+  while n != 1 {
+    res = res * n;
+    n = n - 1;
+  }
+  ret res;
+}";
+    assert_eq!(check_src(src), vec![]);
+
+    let src = "\
+:main {
+  ret y;
+}
+:f a, b { ret c; }
+:g { mut x = 1; mut x = 2; ret x; }
+:f a { ret a; }
+:h a, a { ret a; }";
+    assert_eq!(
+        check_src(src),
+        vec![
+            (6, "function `f` is defined more than once".into()),
+            (2, "undefined variable `y`".into()),
+            (4, "undefined variable `c`".into()),
+            (5, "`x` is already declared".into()),
+            (7, "parameter `a` is listed more than once".into()),
+        ]
+    );
+}
+
+#[test]
+fn test_check_calls() {
+    let src = "\
+extern strlen;
+:main {
+  add(1);
+  add(1, 2, 3);
+  sub(1, 2);
+  prntf(\"%d\\n\", strlen(\"abc\"));
+  printf(\"%d %d\\n\", add(1, 2), 3);
+  extern puts;
+}
+:add a, b { ret a + b; }";
+    assert_eq!(
+        check_src(src),
+        vec![
+            (3, "`add` takes 2 arguments but 1 was supplied".into()),
+            (4, "`add` takes 2 arguments but 3 were supplied".into()),
+            (5, "cannot find function `sub`".into()),
+            (6, "cannot find function `prntf`".into()),
+            (8, "`extern` inside of a function".into()),
+        ]
+    );
+}
+
+#[test]
+fn test_check_use_before_declaration() {
+    let src = "\
+:main {
+  x = 2;
+  mut x = x + 1;
+  mut y = 1;
+  ret y;
+}
+:f { ret x; }";
+    assert_eq!(
+        check_src(src),
+        vec![
+            (2, "`x` is used before its declaration".into()),
+            (3, "`x` is used before its declaration".into()),
+            (7, "undefined variable `x`".into()),
+        ]
+    );
+}
+
+#[test]
+fn test_check_loops() {
+    let src = "\
+:main {
+  mut i = 0;
+  while 1 {
+    i = i + 1;
+    if i < 3 { continue; }
+    :inner { break; }
+    break;
+  }
+  continue;
+}";
+    assert_eq!(
+        check_src(src),
+        vec![
+            (6, "`break` outside of a loop".into()),
+            (9, "`continue` outside of a loop".into()),
+        ]
+    );
+}
+
+#[test]
+fn test_check_for_scope() {
+    let src = "\
+:main {
+  for i in 0..10 { printf(\"%d\\n\", i); }
+  for mut i = 0; i < 10; i = i + 2 { continue; }
+  ret i;
+}";
+    assert_eq!(check_src(src), vec![(4, "`i` is not in scope here".into())]);
+}
+
+#[test]
+fn test_check_shadowing() {
+    let src = "\
+:main n {
+  mut x = 1;
+  if x {
+    mut x = x + 1;
+    mut n = x;
+    mut x = n;
+  }
+  mut n = 2;
+  ret x;
+}";
+    assert_eq!(
+        check_src(src),
+        vec![
+            (6, "`x` is already declared".into()),
+            (8, "`n` is already declared".into()),
+        ]
+    );
+}
+
+#[test]
+fn test_check_globals() {
+    let src = "\
+const N = 10;
+const M = N * 2 + 1;
+global total = M;
+global seed = rand();
+global total = 1;
+mut x = 1;
+:main {
+  const K = 1;
+  for i in 0..N { total = total + i; }
+  N = 3;
+  mut N = 2;
+  N = 3;
+  mut buf = [0; N];
+  ret total;
+}";
+    assert_eq!(
+        check_src(src),
+        vec![
+            (
+                4,
+                "the value of `seed` must be known at compile time".into()
+            ),
+            (5, "`total` is defined more than once".into()),
+            (6, "`mut` outside of a function".into()),
+            (8, "`const` inside of a function".into()),
+            (10, "cannot assign to constant `N`".into()),
+            (13, "the length of `buf` must be a constant above 0".into()),
+        ]
+    );
+}
+
+#[test]
+fn test_check_arrays() {
+    let src = "\
+const LEN = 4;
+:main {
+  mut buf = [0; LEN];
+  mut n = 2;
+  buf[n] = buf[n - 1] + 1;
+  buf[LEN] = 1;
+  n[0] = 1;
+  ret buf;
+}
+:f n {
+  mut bad = [0; n];
+  bad = 1;
+  ret f([1; 2]);
+}";
+    assert_eq!(
+        check_src(src),
+        vec![
+            (6, "index 4 is out of bounds for `buf`".into()),
+            (7, "`n` is not an array".into()),
+            (8, "`buf` is an array".into()),
+            (11, "the length of `bad` must be a constant above 0".into()),
+            (12, "`bad` is an array".into()),
+            (13, "an array can only be the value of a `mut`".into()),
+        ]
+    );
+}
+
+#[test]
+fn test_check_pointers() {
+    let src = "\
+const N = 1;
+global g = 0;
+:main {
+  mut x = 1;
+  mut buf = [0; 4];
+  mut p = &x;
+  *p = *&buf[2] + *&g;
+  scanf(\"%d\", &buf);
+  p = &(x + 1);
+  p = &N;
+  p = &f();
+  ret *p;
+}
+:f { ret 0; }";
+    assert_eq!(
+        check_src(src),
+        vec![
+            (9, "cannot take the address of a temporary value".into()),
+            (10, "cannot take the address of constant `N`".into()),
+            (11, "cannot take the address of a temporary value".into()),
+        ]
+    );
+}
+
+#[test]
+fn test_check_structs() {
+    let src = "\
+struct Point { x, y }
+struct Pair { a, a }
+:main {
+  mut p = Point { x: 1, y: p };
+  mut q = Point { x: 1, z: 2, x: 3 };
+  mut r = Line { from: 0 };
+  p.x = p.y + q.x;
+  p.z = 1;
+  mut n = 1;
+  n.x = 2;
+  ret p;
+}
+:f { struct Inner { a } }";
+    assert_eq!(
+        check_src(src),
+        vec![
+            (2, "field `a` is listed more than once".into()),
+            (4, "`p` is used before its declaration".into()),
+            (5, "`Point` has no field `z`".into()),
+            (5, "field `x` is given more than once".into()),
+            (5, "missing field `y` in `Point`".into()),
+            (6, "cannot find struct `Line`".into()),
+            (8, "`Point` has no field `z`".into()),
+            (10, "`n` is not a struct".into()),
+            (11, "`p` is a struct".into()),
+            (13, "`struct` inside of a function".into()),
+        ]
+    );
+}
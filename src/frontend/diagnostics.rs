@@ -0,0 +1,163 @@
+use std::fmt;
+
+use super::span::Span;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Note => write!(f, "note"),
+        }
+    }
+}
+
+// a message about some part of a sac file, rendered the way rustc does it:
+//
+//   error: undefined variable `x`
+//    --> play.sac:3:9
+//     |
+//   3 |     ret x + 1;
+//     |         ^
+//     = help: declare it with `mut x = ...;` first
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub msg: String,
+    pub span: Span,
+    pub notes: Vec<String>,
+    pub helps: Vec<String>,
+}
+
+#[allow(dead_code)]
+impl Diagnostic {
+    pub fn n(severity: Severity, span: Span, msg: String) -> Self {
+        Diagnostic {
+            severity,
+            msg,
+            span,
+            notes: vec![],
+            helps: vec![],
+        }
+    }
+
+    pub fn error(span: Span, msg: String) -> Self {
+        Diagnostic::n(Severity::Error, span, msg)
+    }
+
+    pub fn warning(span: Span, msg: String) -> Self {
+        Diagnostic::n(Severity::Warning, span, msg)
+    }
+
+    pub fn note(mut self, note: String) -> Self {
+        self.notes.push(note);
+        self
+    }
+
+    pub fn help(mut self, help: String) -> Self {
+        self.helps.push(help);
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    // `src` is the contents of the file the span points into
+    pub fn render(&self, src: &str) -> String {
+        let span = &self.span;
+        let line_no = span.start_line.to_string();
+        let gutter = " ".repeat(line_no.len());
+        let mut out = format!(
+            "{}: {}\n{gutter}--> {}:{}:{}\n",
+            self.severity, self.msg, span.file, span.start_line, span.start_col
+        );
+
+        if let Some(line) = src.lines().nth(span.start_line as usize - 1) {
+            let start = span.start_col as usize - 1;
+            let line_len = line.chars().count();
+            // spans over several lines get underlined up to the end of the first
+            let end = if span.end_line == span.start_line {
+                span.end_col as usize - 1
+            } else {
+                line_len
+            };
+            let width = end.saturating_sub(start).max(1);
+            let indent: String = line
+                .chars()
+                .take(start)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+
+            out += &format!("{gutter} |\n");
+            out += &format!("{line_no} | {line}\n");
+            out += &format!("{gutter} | {indent}{}\n", "^".repeat(width));
+        }
+
+        for note in &self.notes {
+            out += &format!("{gutter} = note: {note}\n");
+        }
+        for help in &self.helps {
+            out += &format!("{gutter} = help: {help}\n");
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+fn span(start_line: i32, start_col: i32, end_line: i32, end_col: i32) -> Span {
+    Span {
+        file: "play.sac".into(),
+        start_line,
+        start_col,
+        end_line,
+        end_col,
+    }
+}
+
+#[test]
+fn test_render() {
+    let src = ":main {\n    ret x + 1;\n}\n";
+    let diag = Diagnostic::error(span(2, 9, 2, 10), "undefined variable `x`".into())
+        .help("declare it with `mut x = ...;` first".into());
+    assert_eq!(
+        diag.render(src),
+        "\
+error: undefined variable `x`
+ --> play.sac:2:9
+  |
+2 |     ret x + 1;
+  |         ^
+  = help: declare it with `mut x = ...;` first
+"
+    );
+}
+
+#[test]
+fn test_render_ranges() {
+    let src = ":main {\n    printf(\"%d\\n\", a, b, c, d, e);\n}\n";
+    let diag = Diagnostic::warning(span(2, 5, 2, 35), "too many arguments".into())
+        .note("only 4 fit in registers".into());
+    assert_eq!(
+        diag.render(src),
+        "\
+warning: too many arguments
+ --> play.sac:2:5
+  |
+2 |     printf(\"%d\\n\", a, b, c, d, e);
+  |     ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+  = note: only 4 fit in registers
+"
+    );
+
+    // a span over several lines is underlined to the end of its first line
+    let diag = Diagnostic::error(span(1, 1, 3, 2), "bad function".into());
+    assert!(diag.render(src).ends_with("1 | :main {\n  | ^^^^^^^\n"));
+}
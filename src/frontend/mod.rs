@@ -1,6 +1,10 @@
 pub mod ast;
+pub mod checker;
+pub mod diagnostics;
 pub mod parser;
 pub mod sac_parser;
 pub mod source;
 pub mod span;
+pub mod typeck;
+pub mod types;
 pub mod xml_parser;
@@ -1,9 +1,102 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Mutex, OnceLock},
+};
+
 use regex::Regex;
 
-pub type ParseResult<'a, Output> = Result<(&'a str, Output), &'a str>;
+use super::source::Source;
+use super::span::Span;
+
+// why a parser failed: where it was and what it would have accepted there.
+// when several alternatives fail, the error that got furthest into the input
+// wins, and alternatives that failed at the same place pool what they expected
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError<'a> {
+    pub at: Source<'a>,
+    pub expected: Vec<String>,
+}
+
+impl<'a> ParseError<'a> {
+    pub fn n(at: Source<'a>, expected: &str) -> Self {
+        ParseError {
+            at,
+            expected: vec![expected.into()],
+        }
+    }
+
+    // a failure that doesn't say what would have worked instead
+    pub fn unexpected(at: Source<'a>) -> Self {
+        ParseError {
+            at,
+            expected: vec![],
+        }
+    }
+
+    pub fn offset(&self) -> usize {
+        self.at.index
+    }
+
+    // folds in a failure recovered from earlier, if it got further
+    pub fn merge_hint(self, hint: Option<ParseError<'a>>) -> ParseError<'a> {
+        match hint {
+            Some(hint) => hint.merge(self),
+            None => self,
+        }
+    }
+
+    pub fn merge(self, other: ParseError<'a>) -> ParseError<'a> {
+        if self.offset() > other.offset() {
+            self
+        } else if self.offset() < other.offset() {
+            other
+        } else {
+            let mut expected = self.expected;
+            for exp in other.expected {
+                if !expected.contains(&exp) {
+                    expected.push(exp);
+                }
+            }
+            ParseError {
+                at: self.at,
+                expected,
+            }
+        }
+    }
+}
+
+impl fmt::Display for ParseError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.expected.as_slice() {
+            [] => write!(f, "unexpected input"),
+            [only] => write!(f, "expected {only}"),
+            [init @ .., last] => write!(f, "expected {} or {last}", init.join(", ")),
+        }
+    }
+}
+
+// keeps whichever of two optional errors got further
+pub fn furthest<'a>(
+    first: Option<ParseError<'a>>,
+    second: Option<ParseError<'a>>,
+) -> Option<ParseError<'a>> {
+    match (first, second) {
+        (Some(first), Some(second)) => Some(first.merge(second)),
+        (first, None) => first,
+        (None, second) => second,
+    }
+}
+
+// a successful parse hands back the remaining input, its output and the
+// furthest failure it ran into and recovered from along the way (e.g. the
+// item that ended a zero_or_more). if whatever comes next fails, that
+// earlier failure may be the one that explains what really went wrong
+pub type ParseResult<'a, Output> =
+    Result<(Source<'a>, Output, Option<ParseError<'a>>), ParseError<'a>>;
 
 pub trait Parser<'a, Output> {
-    fn parse(&self, input: &'a str) -> ParseResult<'a, Output>;
+    fn parse(&self, input: Source<'a>) -> ParseResult<'a, Output>;
 
     fn map<F, NewOutput>(self, map_fn: F) -> BoxedParser<'a, NewOutput>
     where
@@ -73,6 +166,14 @@ pub trait Parser<'a, Output> {
     {
         BoxedParser::new(pair(self, parser2))
     }
+
+    fn label(self, name: &'a str) -> BoxedParser<'a, Output>
+    where
+        Self: Sized + 'a,
+        Output: 'a,
+    {
+        BoxedParser::new(label(self, name))
+    }
 }
 
 pub struct BoxedParser<'a, Output> {
@@ -89,47 +190,81 @@ impl<'a, Output> BoxedParser<'a, Output> {
 }
 
 impl<'a, Output> Parser<'a, Output> for BoxedParser<'a, Output> {
-    fn parse(&self, input: &'a str) -> ParseResult<'a, Output> {
+    fn parse(&self, input: Source<'a>) -> ParseResult<'a, Output> {
         self.p.parse(input)
     }
 }
 
 impl<'a, F, Output> Parser<'a, Output> for F
 where
-    F: Fn(&'a str) -> ParseResult<Output>,
+    F: Fn(Source<'a>) -> ParseResult<'a, Output>,
 {
-    fn parse(&self, input: &'a str) -> ParseResult<'a, Output> {
+    fn parse(&self, input: Source<'a>) -> ParseResult<'a, Output> {
         self(input)
     }
 }
 
+// runs `parser` over a plain string and reports the input left over, which
+// keeps the expected values in tests readable
+#[cfg(test)]
+pub fn parse_str<'a, P, Output>(parser: &P, input: &'a str) -> Result<(&'a str, Output), &'a str>
+where
+    P: Parser<'a, Output>,
+{
+    parser
+        .parse(Source::from(input))
+        .map(|(rest, res, _)| (rest.rest(), res))
+        .map_err(|err| err.at.rest())
+}
+
 #[allow(dead_code)]
-pub fn literal<'a>(exp: &'a str) -> impl Parser<()> {
-    move |input: &'a str| match input.get(0..exp.len()) {
-        Some(next) if next == exp => Ok((&input[exp.len()..], ())),
-        _ => Err(input),
+pub fn literal<'a>(exp: &'a str) -> impl Parser<'a, ()> {
+    move |input: Source<'a>| match input.rest().get(0..exp.len()) {
+        Some(next) if next == exp => Ok((input.advance(exp.len()), (), None)),
+        _ => Err(ParseError::n(input, &format!("`{exp}`"))),
     }
 }
 
 #[test]
 fn test_literal() {
     let parse_pepple = literal("Pepple");
-    assert_eq!(Ok((" Joshua", ())), parse_pepple.parse("Pepple Joshua"));
-    assert_eq!(Ok((" Pepple", ())), parse_pepple.parse("Pepple Pepple"));
-    assert_eq!(Err("Joshua Pepple"), parse_pepple.parse("Joshua Pepple"));
+    assert_eq!(
+        Ok((" Joshua", ())),
+        parse_str(&parse_pepple, "Pepple Joshua")
+    );
+    assert_eq!(
+        Ok((" Pepple", ())),
+        parse_str(&parse_pepple, "Pepple Pepple")
+    );
+    assert_eq!(
+        Err("Joshua Pepple"),
+        parse_str(&parse_pepple, "Joshua Pepple")
+    );
+}
+
+// `exp` compiled. that costs far more than a match, and parsers are built
+// again on every call, so each pattern is only compiled the first time
+fn compiled(exp: &str) -> Regex {
+    static CACHE: OnceLock<Mutex<HashMap<String, Regex>>> = OnceLock::new();
+    let mut cache = CACHE.get_or_init(Default::default).lock().unwrap();
+    if let Some(reg) = cache.get(exp) {
+        return reg.clone();
+    }
+    let reg = Regex::new(exp).unwrap();
+    cache.insert(exp.to_string(), reg.clone());
+    reg
 }
 
 #[allow(dead_code)]
-pub fn match_regex<'a>(exp: &'a str) -> impl Parser<String> {
-    move |input: &'a str| {
-        let reg = Regex::new(exp).unwrap();
-        match reg.find_at(input, 0) {
-            Some(val) if val.start() == 0 => Ok((
-                &input[val.end()..],
-                input[val.start()..val.end()].to_string(),
-            )),
-            _ => Err(input),
-        }
+pub fn match_regex<'a>(exp: &'a str) -> impl Parser<'a, String> {
+    let reg = compiled(exp);
+    move |input: Source<'a>| match reg.find_at(input.rest(), 0) {
+        Some(val) if val.start() == 0 => Ok((
+            input.advance(val.end()),
+            input.rest()[val.start()..val.end()].to_string(),
+            None,
+        )),
+        _ => Err(ParseError::n(input, &format!("match for `{exp}`"))),
     }
 }
 
@@ -138,9 +273,12 @@ fn test_match_regex() {
     let parse_number = match_regex("[0-9]+");
     assert_eq!(
         Ok(("abcde", "12345".to_string())),
-        parse_number.parse("12345abcde")
+        parse_str(&parse_number, "12345abcde")
+    );
+    assert_eq!(
+        Err("Joshua Pepple"),
+        parse_str(&parse_number, "Joshua Pepple")
     );
-    assert_eq!(Err("Joshua Pepple"), parse_number.parse("Joshua Pepple"));
 }
 
 #[allow(dead_code)]
@@ -150,11 +288,13 @@ where
     P2: Parser<'a, R2>,
 {
     move |input| {
-        parser1.parse(input).and_then(|(next_input, result1)| {
-            parser2
-                .parse(next_input)
-                .map(|(final_input, result2)| (final_input, (result1, result2)))
-        })
+        let (next_input, result1, hint1) = parser1.parse(input)?;
+        match parser2.parse(next_input) {
+            Ok((final_input, result2, hint2)) => {
+                Ok((final_input, (result1, result2), furthest(hint1, hint2)))
+            }
+            Err(err) => Err(err.merge_hint(hint1)),
+        }
     }
 }
 
@@ -164,7 +304,7 @@ fn test_pair() {
 
     assert_eq!(
         Ok(("/>", ((), "my_tag".to_string()))),
-        tag_opener.parse("<my_tag/>")
+        parse_str(&tag_opener, "<my_tag/>")
     );
 }
 
@@ -177,7 +317,7 @@ where
     move |input| {
         parser
             .parse(input)
-            .map(|(next_input, res)| (next_input, map_fn(res)))
+            .map(|(next_input, res, hint)| (next_input, map_fn(res), hint))
     }
 }
 
@@ -187,20 +327,20 @@ fn test_map() {
 
     assert_eq!(
         Ok(("/>", "my_tag".to_string())),
-        tag_opener.parse("<my_tag/>")
+        parse_str(&tag_opener, "<my_tag/>")
     );
 }
 
 #[allow(dead_code)]
-pub fn identifier(input: &str) -> ParseResult<String> {
+pub fn identifier(input: Source<'_>) -> ParseResult<'_, String> {
     let mut matched = String::new();
-    let mut chars = input.chars();
+    let mut chars = input.rest().chars();
 
     match chars.next() {
         Some(next) if next.is_alphabetic() => {
             matched.push(next);
         }
-        _ => return Err(input),
+        _ => return Err(ParseError::n(input, "identifier")),
     }
 
     for next in chars {
@@ -215,24 +355,24 @@ pub fn identifier(input: &str) -> ParseResult<String> {
     }
 
     let next_index = matched.len();
-    Ok((&input[next_index..], matched))
+    Ok((input.advance(next_index), matched, None))
 }
 
 #[test]
 fn test_identifier_parser() {
     assert_eq!(
         Ok(("", "i_am_an_identifier".to_string())),
-        identifier("i_am_an_identifier")
+        parse_str(&identifier, "i_am_an_identifier")
     );
 
     assert_eq!(
         Ok((" entirely an identifier", "not".to_string())),
-        identifier("not entirely an identifier")
+        parse_str(&identifier, "not entirely an identifier")
     );
 
     assert_eq!(
         Err("!not entirely an identifier"),
-        identifier("!not entirely an identifier")
+        parse_str(&identifier, "!not entirely an identifier")
     );
 }
 
@@ -262,7 +402,7 @@ fn test_left_and_right() {
 
     assert_eq!(
         Ok(("", ("joshua".to_string(), "pepple".to_string()))),
-        pair(left(first, space), last).parse("joshua pepple")
+        parse_str(&pair(left(first, space), last), "joshua pepple")
     );
 }
 
@@ -271,29 +411,58 @@ pub fn constant<'a, U>(value: U) -> impl Parser<'a, U>
 where
     U: Clone + 'a,
 {
-    move |input: &'a str| Ok((input, value.clone()))
+    move |input: Source<'a>| Ok((input, value.clone(), None))
 }
 
 #[test]
 fn test_constant() {
     let parser = constant(300);
-    assert_eq!(Ok(("doesn't matter", 300)), parser.parse("doesn't matter"));
+    assert_eq!(
+        Ok(("doesn't matter", 300)),
+        parse_str(&parser, "doesn't matter")
+    );
 }
 
 #[allow(dead_code)]
-pub fn number_i32(input: &str) -> ParseResult<i32> {
-    match match_regex("[0-9]+").parse(input) {
-        Ok((new_input, num_str)) => match num_str.parse::<i32>() {
-            Ok(num) => Ok((new_input, num)),
-            Err(_) => Err(input),
+pub fn number_i32(input: Source<'_>) -> ParseResult<'_, i32> {
+    match match_regex("-?[0-9]+").parse(input) {
+        Ok((new_input, num_str, hint)) => match num_str.parse::<i32>() {
+            Ok(num) => Ok((new_input, num, hint)),
+            Err(_) => Err(ParseError::n(input, "number that fits in 32 bits")),
         },
-        Err(err) => Err(err),
+        Err(_) => Err(ParseError::n(input, "number")),
     }
 }
 
 #[test]
 fn test_number_i32() {
-    assert_eq!(Ok(("", 42)), number_i32("42"));
+    assert_eq!(Ok(("", 42)), parse_str(&number_i32, "42"));
+    assert_eq!(Ok(("", i32::MIN)), parse_str(&number_i32, "-2147483648"));
+    assert_eq!(Err("2147483648"), parse_str(&number_i32, "2147483648"));
+}
+
+#[allow(dead_code)]
+pub fn number_i64(input: Source<'_>) -> ParseResult<'_, i64> {
+    match match_regex("-?[0-9]+").parse(input) {
+        Ok((new_input, num_str, hint)) => match num_str.parse::<i64>() {
+            Ok(num) => Ok((new_input, num, hint)),
+            Err(_) => Err(ParseError::n(input, "number that fits in 64 bits")),
+        },
+        Err(_) => Err(ParseError::n(input, "number")),
+    }
+}
+
+#[test]
+fn test_number_i64() {
+    assert_eq!(Ok(("", 2147483648)), parse_str(&number_i64, "2147483648"));
+    assert_eq!(
+        Ok(("", i64::MIN)),
+        parse_str(&number_i64, "-9223372036854775808")
+    );
+    assert_eq!(
+        Err("9223372036854775808"),
+        parse_str(&number_i64, "9223372036854775808")
+    );
 }
 
 #[allow(dead_code)]
@@ -302,17 +471,17 @@ where
     P: Parser<'a, R>,
     R: Clone + 'a,
 {
-    move |input: &'a str| match parser.parse(input) {
-        Ok((new_input, res)) => Ok((new_input, vec![res])),
-        Err(_) => constant(vec![value.clone()]).parse(input),
+    move |input: Source<'a>| match parser.parse(input) {
+        Ok((new_input, res, hint)) => Ok((new_input, vec![res], hint)),
+        Err(err) => Ok((input, vec![value.clone()], Some(err))),
     }
 }
 
 #[test]
 fn test_maybe() {
     let parser = maybe(match_regex("[0-9]+"), "".into());
-    assert_eq!(parser.parse("1234"), Ok(("", vec!["1234".into()])));
-    assert_eq!(parser.parse("abcd"), Ok(("abcd", vec!["".into()])));
+    assert_eq!(parse_str(&parser, "1234"), Ok(("", vec!["1234".into()])));
+    assert_eq!(parse_str(&parser, "abcd"), Ok(("abcd", vec!["".into()])));
 }
 
 #[allow(dead_code)]
@@ -322,13 +491,18 @@ where
 {
     move |mut input| {
         let mut results = vec![];
-
-        while let Ok((next_input, item)) = parser.parse(input) {
-            input = next_input;
-            results.push(item);
+        let mut hint = None;
+
+        loop {
+            match parser.parse(input) {
+                Ok((next_input, item, item_hint)) => {
+                    input = next_input;
+                    results.push(item);
+                    hint = furthest(hint, item_hint);
+                }
+                Err(err) => return Ok((input, results, furthest(hint, Some(err)))),
+            }
         }
-
-        Ok((input, results))
     }
 }
 
@@ -337,22 +511,20 @@ pub fn one_or_more<'a, P, A>(parser: P) -> impl Parser<'a, Vec<A>>
 where
     P: Parser<'a, A>,
 {
-    move |mut input| {
-        let mut results = vec![];
-
-        if let Ok((next_input, head)) = parser.parse(input) {
-            input = next_input;
-            results.push(head);
-        } else {
-            return Err(input);
-        }
-
-        while let Ok((next_input, item)) = parser.parse(input) {
-            input = next_input;
-            results.push(item);
+    move |input| {
+        let (mut input, head, mut hint) = parser.parse(input)?;
+        let mut results = vec![head];
+
+        loop {
+            match parser.parse(input) {
+                Ok((next_input, item, item_hint)) => {
+                    input = next_input;
+                    results.push(item);
+                    hint = furthest(hint, item_hint);
+                }
+                Err(err) => return Ok((input, results, furthest(hint, Some(err)))),
+            }
         }
-
-        Ok((input, results))
     }
 }
 
@@ -361,11 +533,11 @@ fn test_zero_or_more() {
     let parser = zero_or_more(match_regex("ha"));
     assert_eq!(
         Ok(("", vec!["ha".into(), "ha".into(), "ha".into(), "ha".into()])),
-        parser.parse("hahahaha")
+        parse_str(&parser, "hahahaha")
     );
 
-    assert_eq!(Ok(("ahah", vec![])), parser.parse("ahah"));
-    assert_eq!(Ok(("", vec![])), parser.parse(""));
+    assert_eq!(Ok(("ahah", vec![])), parse_str(&parser, "ahah"));
+    assert_eq!(Ok(("", vec![])), parse_str(&parser, ""));
 }
 
 #[test]
@@ -373,18 +545,37 @@ fn test_one_or_more() {
     let parser = one_or_more(match_regex("ha"));
     assert_eq!(
         Ok(("", vec!["ha".into(), "ha".into(), "ha".into(), "ha".into()])),
-        parser.parse("hahahaha")
+        parse_str(&parser, "hahahaha")
     );
 
-    assert_eq!(Err("ahah"), parser.parse("ahah"));
-    assert_eq!(Err(""), parser.parse(""));
+    assert_eq!(Err("ahah"), parse_str(&parser, "ahah"));
+    assert_eq!(Err(""), parse_str(&parser, ""));
+}
+
+#[allow(dead_code)]
+pub fn end_of_input(input: Source<'_>) -> ParseResult<'_, ()> {
+    if input.rest().is_empty() {
+        Ok((input, (), None))
+    } else {
+        Err(ParseError::n(input, "end of input"))
+    }
+}
+
+#[test]
+fn test_end_of_input() {
+    assert_eq!(Ok(("", ())), parse_str(&end_of_input, ""));
+    assert_eq!(Err("}"), parse_str(&end_of_input, "}"));
+    assert_eq!(
+        Ok(("", "abc".into())),
+        parse_str(&identifier.and_left(end_of_input), "abc")
+    );
 }
 
 #[allow(dead_code)]
-pub fn any_char(input: &str) -> ParseResult<char> {
-    match input.chars().next() {
-        Some(next) => Ok((&input[next.len_utf8()..], next)),
-        None => Err(input),
+pub fn any_char(input: Source<'_>) -> ParseResult<'_, char> {
+    match input.rest().chars().next() {
+        Some(next) => Ok((input.advance(next.len_utf8()), next, None)),
+        None => Err(ParseError::n(input, "any character")),
     }
 }
 
@@ -395,20 +586,20 @@ where
     F: Fn(&A) -> bool,
 {
     move |input| {
-        if let Ok((next_input, res)) = parser.parse(input) {
-            if predicate(&res) {
-                return Ok((next_input, res));
-            }
+        let (next_input, res, hint) = parser.parse(input)?;
+        if predicate(&res) {
+            Ok((next_input, res, hint))
+        } else {
+            Err(ParseError::unexpected(input).merge_hint(hint))
         }
-        Err(input)
     }
 }
 
 #[test]
 fn test_predicate() {
     let parser = pred(any_char, |c| *c == 'o');
-    assert_eq!(Ok(("mg", 'o')), parser.parse("omg"));
-    assert_eq!(Err("lol"), parser.parse("lol"));
+    assert_eq!(Ok(("mg", 'o')), parse_str(&parser, "omg"));
+    assert_eq!(Err("lol"), parse_str(&parser, "lol"));
 }
 
 #[allow(dead_code)]
@@ -434,14 +625,20 @@ where
 {
     move |input| match parser1.parse(input) {
         ok @ Ok(_) => ok,
-        Err(_) => parser2.parse(input),
+        Err(err1) => match parser2.parse(input) {
+            Ok((next_input, res, hint)) => Ok((next_input, res, furthest(Some(err1), hint))),
+            Err(err2) => Err(err1.merge(err2)),
+        },
     }
 }
 
 #[test]
 fn test_either() {
     let num = match_regex(r"[0-9]{3}");
-    assert_eq!(either(num, identifier).parse("abc"), Ok(("", "abc".into())))
+    assert_eq!(
+        parse_str(&either(num, identifier), "abc"),
+        Ok(("", "abc".into()))
+    )
 }
 
 #[allow(dead_code)]
@@ -451,20 +648,23 @@ where
     P2: Parser<'a, R>,
 {
     move |input| match parser1.parse(input) {
-        Ok((new_input, res)) => Ok((new_input, res)),
-        Err(_) => parser2.parse(input),
+        Ok((new_input, res, hint)) => Ok((new_input, res, hint)),
+        Err(err1) => match parser2.parse(input) {
+            Ok((new_input, res, hint)) => Ok((new_input, res, furthest(Some(err1), hint))),
+            Err(err2) => Err(err1.merge(err2)),
+        },
     }
 }
 
 #[test]
 fn test_or() {
     let parser = match_regex("[0-9]+").or(identifier);
-    assert_eq!(parser.parse("12345"), Ok(("", "12345".into())));
+    assert_eq!(parse_str(&parser, "12345"), Ok(("", "12345".into())));
     assert_eq!(
-        parser.parse("identifier_135"),
+        parse_str(&parser, "identifier_135"),
         Ok(("35", "identifier_1".into()))
     );
-    assert_eq!(parser.parse("!23 identifier"), Err("!23 identifier"));
+    assert_eq!(parse_str(&parser, "!23 identifier"), Err("!23 identifier"));
 }
 
 #[allow(dead_code)]
@@ -473,9 +673,12 @@ where
     P1: Parser<'a, R1>,
     P2: Parser<'a, R2>,
 {
-    move |input| match parser1.parse(input) {
-        Ok((new_input, _)) => parser2.parse(new_input),
-        Err(_) => Err(input),
+    move |input| {
+        let (new_input, _, hint1) = parser1.parse(input)?;
+        match parser2.parse(new_input) {
+            Ok((final_input, res, hint2)) => Ok((final_input, res, furthest(hint1, hint2))),
+            Err(err) => Err(err.merge_hint(hint1)),
+        }
     }
 }
 
@@ -485,15 +688,12 @@ where
     P1: Parser<'a, R1>,
     P2: Parser<'a, R2>,
 {
-    move |input| match parser1.parse(input) {
-        Ok((new_input, res)) => {
-            if let Ok((final_input, _)) = parser2.parse(new_input) {
-                Ok((final_input, res))
-            } else {
-                Err(new_input)
-            }
+    move |input| {
+        let (new_input, res, hint1) = parser1.parse(input)?;
+        match parser2.parse(new_input) {
+            Ok((final_input, _, hint2)) => Ok((final_input, res, furthest(hint1, hint2))),
+            Err(err) => Err(err.merge_hint(hint1)),
         }
-        Err(_) => Err(input),
     }
 }
 
@@ -502,7 +702,10 @@ fn test_and_right() {
     let parser =
         literal("joshua").and_right(any_char.pred(|c| c.is_whitespace()).and_right(identifier));
 
-    assert_eq!(parser.parse("joshua pepple"), Ok(("", "pepple".into())))
+    assert_eq!(
+        parse_str(&parser, "joshua pepple"),
+        Ok(("", "pepple".into()))
+    )
 }
 
 #[allow(dead_code)]
@@ -512,12 +715,108 @@ where
     NextParser: Parser<'a, B>,
     F: Fn(A) -> NextParser,
 {
-    move |input| match parser.parse(input) {
-        Ok((next_input, parsed)) => f(parsed).parse(next_input),
-        Err(err) => Err(err),
+    move |input| {
+        let (next_input, parsed, hint1) = parser.parse(input)?;
+        match f(parsed).parse(next_input) {
+            Ok((final_input, res, hint2)) => Ok((final_input, res, furthest(hint1, hint2))),
+            Err(err) => Err(err.merge_hint(hint1)),
+        }
+    }
+}
+
+// when `parser` fails without consuming anything, says it expected `name`
+// rather than listing every token that could have started it
+#[allow(dead_code)]
+pub fn label<'a, P, A>(parser: P, name: &'a str) -> impl Parser<'a, A>
+where
+    P: Parser<'a, A>,
+{
+    move |input: Source<'a>| {
+        let relabel = |err: ParseError<'a>| {
+            if err.offset() == input.index {
+                ParseError::n(err.at, name)
+            } else {
+                err
+            }
+        };
+        match parser.parse(input) {
+            Ok((next_input, res, hint)) => Ok((next_input, res, hint.map(relabel))),
+            Err(err) => Err(relabel(err)),
+        }
+    }
+}
+
+#[test]
+fn test_label() {
+    let operand = || number_i32.map(|num| num.to_string()).or(identifier);
+    let parser = label(operand(), "operand");
+    let err = parser.parse(Source::from("+ 1")).unwrap_err();
+    assert_eq!(err.expected, vec!["operand".to_string()]);
+    assert_eq!(err.to_string(), "expected operand");
+
+    let err = operand().parse(Source::from("+ 1")).unwrap_err();
+    assert_eq!(err.to_string(), "expected number or identifier");
+
+    // failures further in keep their own expectations
+    let parser = label(literal("(").and_right(number_i32), "group");
+    let err = parser.parse(Source::from("(x")).unwrap_err();
+    assert_eq!(
+        (err.offset(), err.to_string()),
+        (1, "expected number".into())
+    );
+}
+
+#[test]
+fn test_furthest_failure() {
+    // `or` keeps the alternative that got further
+    let parser = literal("(")
+        .and_right(number_i32)
+        .and_left(literal(")"))
+        .or(number_i32);
+    let err = parser.parse(Source::from("(12]")).unwrap_err();
+    assert_eq!((err.offset(), err.to_string()), (3, "expected `)`".into()));
+
+    // a failure swallowed by zero_or_more resurfaces if what follows fails
+    let item = literal("[").and_right(number_i32).and_left(literal("]"));
+    let parser = zero_or_more(item).and_left(end_of_input);
+    let err = parser.parse(Source::from("[1][2][x]")).unwrap_err();
+    assert_eq!(
+        (err.offset(), err.to_string()),
+        (7, "expected number".into())
+    );
+
+    // unless the failure is behind where parsing ended up
+    let (rest, _, _) = literal("a")
+        .or(literal("ab"))
+        .parse(Source::from("ab"))
+        .unwrap();
+    assert_eq!(rest.rest(), "b");
+}
+
+// pairs the output of `parser` with the span of the input it consumed
+#[allow(dead_code)]
+pub fn spanned<'a, P, A>(parser: P) -> impl Parser<'a, (A, Span)>
+where
+    P: Parser<'a, A>,
+{
+    move |input: Source<'a>| {
+        parser
+            .parse(input)
+            .map(|(next_input, res, hint)| (next_input, (res, input.span_to(&next_input)), hint))
     }
 }
 
+#[test]
+fn test_spanned() {
+    let parser = right(space0(), spanned(identifier));
+    let (rest, (name, span), _) = parser.parse(Source::new("t.sac", "\n  abc def")).unwrap();
+    assert_eq!(rest.rest(), " def");
+    assert_eq!(name, "abc");
+    assert_eq!(span.file, "t.sac");
+    assert_eq!((span.start_line, span.start_col), (2, 3));
+    assert_eq!((span.end_line, span.end_col), (2, 6));
+}
+
 #[allow(dead_code)]
 pub fn whitespace_wrap<'a, P, A>(parser: P) -> impl Parser<'a, A>
 where
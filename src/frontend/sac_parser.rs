@@ -2,59 +2,109 @@ use closure::closure;
 
 use super::ast::*;
 use super::parser::*;
+use super::source::Source;
 use super::span::Span;
+use super::types::Type;
 
 #[allow(dead_code)]
-fn ignored(input: &str) -> ParseResult<()> {
+fn ignored(input: Source<'_>) -> ParseResult<'_, ()> {
     let whitespace = match_regex(r"[ \n\r\t]+");
     let comments = match_regex(r"[/][/].*").or(match_regex(r"(?s)[/][*].*[*][/]"));
+    // whitespace and comments are never what a failed parse was looking
+    // for, so they don't leave a hint behind
     zero_or_more(whitespace.or(comments))
-        .map(|_| ())
         .parse(input)
+        .map(|(next_input, _, _)| (next_input, (), None))
 }
 
+// skips whitespace and comments before running `parser`, so the span it
+// reports starts at the first real character
 #[allow(dead_code)]
-fn id(input: &str) -> ParseResult<AST> {
-    identifier
-        .map(|x| -> AST {
-            AST::Identifier {
-                name: x,
-                span: Span::new_dud(),
-            }
-        })
+fn located<'a, P, A>(parser: P) -> impl Parser<'a, (A, Span)>
+where
+    P: Parser<'a, A> + 'a,
+    A: 'a,
+{
+    ignored.and_right(spanned(parser))
+}
+
+// names what is missing when `parser` fails right where it starts, so errors
+// read "expected expression" instead of listing every token an expression
+// can start with
+#[allow(dead_code)]
+fn expecting<'a, P, A>(name: &'a str, parser: P) -> impl Parser<'a, A>
+where
+    P: Parser<'a, A> + 'a,
+    A: 'a,
+{
+    ignored.and_right(parser.label(name))
+}
+
+// parses `input` and checks what is left over and, ignoring spans, the
+// AST that came out
+#[cfg(test)]
+fn assert_parses<'a, P>(parser: P, input: &'a str, rest: &str, expected: AST)
+where
+    P: Parser<'a, AST>,
+{
+    match parse_str(&parser, input) {
+        Ok((left_over, ast)) => {
+            assert_eq!(left_over, rest);
+            assert!(
+                ast.equals(&expected),
+                "{input:?} parsed into\n{ast:#?}\nexpected\n{expected:#?}"
+            );
+        }
+        Err(stopped) => panic!("{input:?} stopped parsing at {stopped:?}"),
+    }
+}
+
+// letters, digits and `_`, not starting with a digit. unlike
+// `parser::identifier` a sac name never contains `-`, so `n-1` is a
+// subtraction
+#[allow(dead_code)]
+fn name(input: Source<'_>) -> ParseResult<'_, String> {
+    match_regex("[A-Za-z_][A-Za-z0-9_]*")
+        .label("identifier")
+        .parse(input)
+}
+
+#[allow(dead_code)]
+fn id(input: Source<'_>) -> ParseResult<'_, AST> {
+    located(name)
+        .map(|(name, span)| AST::Identifier { name, span })
         .parse(input)
 }
 
 #[allow(dead_code)]
-fn sidentifier(input: &str) -> ParseResult<String> {
-    ignored.and_right(identifier).parse(input)
+fn sidentifier(input: Source<'_>) -> ParseResult<'_, String> {
+    ignored.and_right(name).parse(input)
 }
 
 #[test]
 fn test_id() {
-    assert_eq!(
-        Ok((
-            "",
-            AST::Identifier {
-                name: "abcd_1".into(),
-                span: Span::new_dud()
-            }
-        )),
-        id("abcd_1")
+    assert_parses(
+        id,
+        "abcd_1",
+        "",
+        AST::Identifier {
+            name: "abcd_1".into(),
+            span: Span::new_dud(),
+        },
     );
 }
 
 #[allow(dead_code)]
-fn expression(input: &str) -> ParseResult<AST> {
-    ignored.and_right(comparison).parse(input)
+fn expression(input: Source<'_>) -> ParseResult<'_, AST> {
+    ignored.and_right(logical_or).parse(input)
 }
 
 #[allow(dead_code)]
-fn args(input: &str) -> ParseResult<Vec<AST>> {
+fn args(input: Source<'_>) -> ParseResult<'_, Vec<AST>> {
     expression
         .and_then(|arg| {
             zero_or_more(sliteral(",").and_right(expression))
-                .and_then(move |args| constant(vec![vec![arg.clone()], args].concat()))
+                .and_then(move |args| constant([vec![arg.clone()], args].concat()))
         })
         .or(constant(vec![]))
         .parse(input)
@@ -62,334 +112,703 @@ fn args(input: &str) -> ParseResult<Vec<AST>> {
 
 #[test]
 fn test_args() {
-    assert_eq!(
-        args("a, b, c"),
-        Ok((
-            "",
-            vec![
+    let (rest, parsed) = parse_str(&args, "a, b, c").unwrap();
+    let expected = [
+        AST::Identifier {
+            name: "a".into(),
+            span: Span::new_dud(),
+        },
+        AST::Identifier {
+            name: "b".into(),
+            span: Span::new_dud(),
+        },
+        AST::Identifier {
+            name: "c".into(),
+            span: Span::new_dud(),
+        },
+    ];
+    assert_eq!(rest, "");
+    assert_eq!(parsed.len(), expected.len());
+    assert!(parsed
+        .iter()
+        .zip(expected.iter())
+        .all(|(arg, exp)| arg.equals(exp)));
+    assert_eq!(parse_str(&args, ""), Ok(("", vec![])));
+}
+
+#[allow(dead_code)]
+fn sliteral(exp: &str) -> impl Parser<'_, String> {
+    ignored
+        .and_right(literal(exp))
+        .map(move |_| exp.to_string())
+}
+
+// `kw` as a whole word: `elsewhere` is a name, not `else` and `where`
+#[allow(dead_code)]
+fn keyword<'a>(kw: &'a str) -> impl Parser<'a, String> {
+    move |input: Source<'a>| {
+        let (start, _, _) = ignored.parse(input)?;
+        let (next, res, hint) = sliteral(kw).parse(start)?;
+        match next.rest().chars().next() {
+            Some(c) if c.is_alphanumeric() || c == '_' => {
+                Err(ParseError::n(start, &format!("`{kw}`")))
+            }
+            _ => Ok((next, res, hint)),
+        }
+    }
+}
+
+#[allow(dead_code)]
+fn sident(input: Source<'_>) -> ParseResult<'_, String> {
+    ignored.and_right(name).parse(input)
+}
+
+#[allow(dead_code)]
+fn call(input: Source<'_>) -> ParseResult<'_, AST> {
+    located(sident.and_then(|called| {
+        sliteral("(").and_right(
+            args.and_then(move |args| sliteral(")").and_right(constant((called.clone(), args)))),
+        )
+    }))
+    .map(|((called, args), span)| AST::Call { called, args, span })
+    .parse(input)
+}
+
+// `name.field`, read or written. `single` keeps `i..n` a range
+#[allow(dead_code)]
+fn dotted(input: Source<'_>) -> ParseResult<'_, ((String, String), Span)> {
+    located(
+        sident
+            .and_left(single("."))
+            .and_tuple(expecting("field name", sidentifier)),
+    )
+    .parse(input)
+}
+
+#[allow(dead_code)]
+fn field(input: Source<'_>) -> ParseResult<'_, AST> {
+    dotted
+        .map(|((name, field), span)| AST::Field { span, name, field })
+        .parse(input)
+}
+
+// `field: value`
+#[allow(dead_code)]
+fn field_init(input: Source<'_>) -> ParseResult<'_, (String, AST)> {
+    sidentifier
+        .and_left(sliteral(":"))
+        .and_tuple(expecting("expression after `:`", expression))
+        .parse(input)
+}
+
+// `Name { field: value, ... }`, only as the value of a declaration, where it
+// cannot be mistaken for the block after an `if` or `while` condition
+#[allow(dead_code)]
+fn struct_lit(input: Source<'_>) -> ParseResult<'_, AST> {
+    located(sident.and_left(sliteral("{")).and_then(|name| {
+        field_init
+            .and_then(|first| {
+                zero_or_more(sliteral(",").and_right(field_init))
+                    .map(move |rest| [vec![first.clone()], rest].concat())
+            })
+            .or(constant(vec![]))
+            .and_left(maybe(sliteral(","), String::new()))
+            .and_left(sliteral("}"))
+            .map(move |fields| (name.clone(), fields))
+    }))
+    .map(|((name, fields), span)| AST::StructLit { span, name, fields })
+    .parse(input)
+}
+
+// `struct Name { field, ... }`
+#[allow(dead_code)]
+fn struct_s(input: Source<'_>) -> ParseResult<'_, AST> {
+    located(
+        keyword("struct")
+            .and_right(expecting("struct name after `struct`", sidentifier))
+            .and_left(sliteral("{"))
+            .and_tuple(params)
+            .and_left(maybe(sliteral(","), String::new()))
+            .and_left(sliteral("}")),
+    )
+    .map(|((name, fields), span)| AST::StructDef { span, name, fields })
+    .parse(input)
+}
+
+#[test]
+fn test_structs() {
+    let num = |num| AST::Number {
+        num,
+        span: Span::new_dud(),
+    };
+    assert_parses(
+        statement,
+        "struct Point { x, y }",
+        "",
+        AST::StructDef {
+            span: Span::new_dud(),
+            name: "Point".into(),
+            fields: vec!["x".into(), "y".into()],
+        },
+    );
+    assert_parses(
+        statement,
+        "mut p = Point { x: 1, y: 2, };",
+        "",
+        AST::Variable {
+            span: Span::new_dud(),
+            name: "p".into(),
+            ty: None,
+            value: Box::new(AST::StructLit {
+                span: Span::new_dud(),
+                name: "Point".into(),
+                fields: vec![("x".into(), num(1)), ("y".into(), num(2))],
+            }),
+        },
+    );
+    assert_parses(
+        statement,
+        "p.x = p.y;",
+        "",
+        AST::FieldAssign {
+            span: Span::new_dud(),
+            name: "p".into(),
+            field: "x".into(),
+            value: Box::new(AST::Field {
+                span: Span::new_dud(),
+                name: "p".into(),
+                field: "y".into(),
+            }),
+        },
+    );
+    // a range is not a field access
+    let (rest, _) = parse_str(&for_s, "for i in a..b { }").unwrap();
+    assert_eq!(rest, "");
+}
+
+// `name[index]`, read or written
+#[allow(dead_code)]
+fn indexed(input: Source<'_>) -> ParseResult<'_, ((String, AST), Span)> {
+    located(sident.and_then(|name| {
+        sliteral("[")
+            .and_right(expecting("index", expression))
+            .and_then(move |index| sliteral("]").and_right(constant((name.clone(), index))))
+    }))
+    .parse(input)
+}
+
+#[allow(dead_code)]
+fn index(input: Source<'_>) -> ParseResult<'_, AST> {
+    indexed
+        .map(|((name, index), span)| AST::Index {
+            span,
+            name,
+            index: Box::new(index),
+        })
+        .parse(input)
+}
+
+// `[value; len]`, an array of `len` copies of `value`
+#[allow(dead_code)]
+fn array(input: Source<'_>) -> ParseResult<'_, AST> {
+    located(sliteral("[").and_right(expression).and_then(|value| {
+        sliteral(";")
+            .and_right(expecting("array length", expression))
+            .and_then(move |len| sliteral("]").and_right(constant((value.clone(), len))))
+    }))
+    .map(|((value, len), span)| AST::Array {
+        span,
+        value: Box::new(value),
+        len: Box::new(len),
+    })
+    .parse(input)
+}
+
+#[test]
+fn test_index_and_array() {
+    let num = |num| {
+        Box::new(AST::Number {
+            num,
+            span: Span::new_dud(),
+        })
+    };
+    assert_parses(
+        atom,
+        "buf[ i + 1 ]",
+        "",
+        AST::Index {
+            span: Span::new_dud(),
+            name: "buf".into(),
+            index: Box::new(AST::Add {
+                lhs: Box::new(AST::Identifier {
+                    name: "i".into(),
+                    span: Span::new_dud(),
+                }),
+                rhs: num(1),
+            }),
+        },
+    );
+    assert_parses(
+        atom,
+        "[0; 16]",
+        "",
+        AST::Array {
+            span: Span::new_dud(),
+            value: num(0),
+            len: num(16),
+        },
+    );
+    assert_parses(
+        statement,
+        "buf[2] = 7;",
+        "",
+        AST::IndexAssign {
+            span: Span::new_dud(),
+            name: "buf".into(),
+            index: num(2),
+            value: num(7),
+        },
+    );
+}
+
+#[test]
+fn test_call() {
+    assert_parses(
+        call,
+        "fib(a,      b,      c)",
+        "",
+        AST::Call {
+            called: "fib".into(),
+            args: vec![
                 AST::Identifier {
                     name: "a".into(),
-                    span: Span::new_dud()
+                    span: Span::new_dud(),
                 },
                 AST::Identifier {
                     name: "b".into(),
-                    span: Span::new_dud()
+                    span: Span::new_dud(),
                 },
                 AST::Identifier {
                     name: "c".into(),
-                    span: Span::new_dud()
-                }
+                    span: Span::new_dud(),
+                },
             ],
-        ))
+            span: Span::new_dud(),
+        },
     );
-    assert_eq!(args(""), Ok(("", vec![],)))
-}
 
-#[allow(dead_code)]
-fn sliteral(exp: &str) -> impl Parser<String> {
-    ignored.and_right(match_regex(exp))
+    assert_parses(
+        call,
+        "printf(\"Hello, World!\")",
+        "",
+        AST::Call {
+            called: "printf".into(),
+            args: vec![AST::StrLiteral {
+                literal: "Hello, World!".into(),
+                span: Span::new_dud(),
+            }],
+            span: Span::new_dud(),
+        },
+    );
 }
 
 #[allow(dead_code)]
-fn sident(input: &str) -> ParseResult<String> {
-    ignored.and_right(identifier).parse(input)
+fn number(input: Source<'_>) -> ParseResult<'_, AST> {
+    located(number_i64)
+        .map(|(num, span)| AST::Number { num, span })
+        .parse(input)
 }
 
 #[allow(dead_code)]
-fn call(input: &str) -> ParseResult<AST> {
-    sident
-        .and_then(|called| {
-            sliteral("[(]").and_right(args.and_then(move |args| {
-                sliteral("[)]").and_right(constant(AST::Call {
-                    called: called.clone(),
-                    args,
-                    span: Span::new_dud(),
-                }))
-            }))
-        })
-        .parse(input)
+fn sac_str(input: Source<'_>) -> ParseResult<'_, AST> {
+    located(
+        sliteral("\"")
+            .and_right(zero_or_more(any_char.pred(|c| *c != '"')).and_left(sliteral("\""))),
+    )
+    .map(|(chars, span)| AST::StrLiteral {
+        literal: chars.into_iter().collect(),
+        span,
+    })
+    .parse(input)
 }
 
 #[test]
-fn test_call() {
-    assert_eq!(
-        call("fib(a,      b,      c)"),
-        Ok((
-            "",
-            AST::Call {
-                called: "fib".into(),
-                args: vec![
-                    AST::Identifier {
-                        name: "a".into(),
-                        span: Span::new_dud()
-                    },
-                    AST::Identifier {
-                        name: "b".into(),
-                        span: Span::new_dud()
-                    },
-                    AST::Identifier {
-                        name: "c".into(),
-                        span: Span::new_dud()
-                    }
-                ],
-                span: Span::new_dud()
-            }
-        ))
-    );
-
-    assert_eq!(
-        call("printf(\"Hello, World!\")"),
-        Ok((
-            "",
-            AST::Call {
-                called: "printf".into(),
-                args: vec![AST::StrLiteral {
-                    literal: "Hello, World!".into(),
-                }],
-                span: Span::new_dud()
-            }
-        ))
-    );
+fn test_sac_str() {
+    assert_parses(
+        sac_str,
+        "\"Hello, World!\"",
+        "",
+        AST::StrLiteral {
+            literal: "Hello, World!".into(),
+            span: Span::new_dud(),
+        },
+    )
 }
 
 #[allow(dead_code)]
-fn number(input: &str) -> ParseResult<AST> {
-    number_i32
-        .map(|num| AST::Number {
-            num,
+fn chr(input: Source<'_>) -> ParseResult<'_, AST> {
+    located(
+        sliteral("'")
+            .and_right(any_char)
+            .and_then(|character| sliteral("'").and_right(constant(character))),
+    )
+    .map(|(ch, span)| AST::Char { ch, span })
+    .parse(input)
+}
+
+#[test]
+fn test_chr() {
+    assert_parses(
+        chr,
+        "'A'",
+        "",
+        AST::Char {
+            ch: 'A',
             span: Span::new_dud(),
-        })
-        .parse(input)
+        },
+    )
 }
 
 #[allow(dead_code)]
-fn sac_str(input: &str) -> ParseResult<AST> {
-    sliteral("\"")
-        .and_right(zero_or_more(any_char.pred(|c| *c != '"')).and_left(sliteral("\"")))
-        .map(|chars| AST::StrLiteral {
-            literal: chars.into_iter().collect(),
+fn boolean(input: Source<'_>) -> ParseResult<'_, AST> {
+    located(keyword("true").or(keyword("false")))
+        .map(|(value, span)| AST::Bool {
+            value: value == "true",
+            span,
         })
         .parse(input)
 }
 
-#[test]
-fn test_sac_str() {
-    assert_eq!(
-        Ok((
-            "",
-            AST::StrLiteral {
-                literal: "Hello, World!".into()
-            }
-        )),
-        sac_str("\"Hello, World!\"")
+// `int`, `i8` and the other integers, `bool`, `char`, `string`, a struct's
+// name, `*type` for a pointer or `[type; len]` for an array
+#[allow(dead_code)]
+fn ty(input: Source<'_>) -> ParseResult<'_, Type> {
+    expecting(
+        "type",
+        sliteral("*")
+            .and_right(ty)
+            .map(|to| Type::Pointer(Box::new(to)))
+            .or(sliteral("[")
+                .and_right(ty)
+                .and_left(sliteral(";"))
+                .and_tuple(expecting(
+                    "array length above 0",
+                    number_i32.pred(|len| *len > 0),
+                ))
+                .and_left(sliteral("]"))
+                .map(|(of, len)| Type::Array(Box::new(of), len)))
+            .or(sidentifier.map(|name| Type::named(&name))),
     )
+    .parse(input)
 }
 
+// the `: type` after a variable or parameter name, which can be left out
 #[allow(dead_code)]
-fn chr(input: &str) -> ParseResult<AST> {
-    sliteral("[']")
-        .and_right(any_char)
-        .and_then(|character| {
-            sliteral("[']").and_then(move |_| {
-                constant(AST::Number {
-                    num: character as i32,
-                    span: Span::new_dud(),
-                })
-            })
-        })
+fn annotation(input: Source<'_>) -> ParseResult<'_, Option<Type>> {
+    maybe(sliteral(":").and_right(ty).map(Some), None)
+        .map(|mut found| found.pop().flatten())
         .parse(input)
 }
 
 #[test]
-fn test_chr() {
+fn test_annotation() {
+    let (rest, found) = parse_str(&annotation, ": **char = 1").unwrap();
+    assert_eq!(rest, " = 1");
+    let to_char = Type::Pointer(Box::new(Type::Char));
+    assert_eq!(found, Some(Type::Pointer(Box::new(to_char))));
+    assert_eq!(parse_str(&annotation, " = 1"), Ok((" = 1", None)));
     assert_eq!(
-        chr("'A'"),
-        Ok((
-            "",
-            AST::Number {
-                num: 65,
-                span: Span::new_dud()
-            }
-        ))
-    )
+        parse_str(&annotation, ": Point"),
+        Ok(("", Some(Type::Struct("Point".into()))))
+    );
+    assert_eq!(
+        parse_str(&annotation, ": [ u8 ; 16 ]"),
+        Ok(("", Some(Type::Array(Box::new(Type::U8), 16))))
+    );
+    assert!(parse_str(&ty, "[u8; 0]").is_err());
+    assert!(parse_str(&ty, "[u8; -1]").is_err());
+    assert_eq!(parse_str(&annotation, ": i32"), Ok(("", Some(Type::Int))));
 }
 
 #[allow(dead_code)]
-fn atom(input: &str) -> ParseResult<AST> {
+fn atom(input: Source<'_>) -> ParseResult<'_, AST> {
     ignored
         .and_right(
-            call.or(id)
+            call.or(index)
+                .or(field)
+                .or(boolean)
+                .or(id)
+                .or(array)
                 .or(number)
                 .or(chr)
                 .or(sac_str)
-                .or(sliteral("[(]")
+                .or(sliteral("(")
                     .and_right(expression)
-                    .and_then(|expr| sliteral("[)]").and_right(constant(expr)))),
+                    .and_then(|expr| sliteral(")").and_right(constant(expr)))),
         )
         .parse(input)
 }
 
 #[test]
 fn test_atom() {
-    assert_eq!(
-        atom("a"),
-        Ok((
-            "",
-            AST::Identifier {
-                name: "a".into(),
-                span: Span::new_dud(),
-            }
-        ))
+    assert_parses(
+        atom,
+        "a",
+        "",
+        AST::Identifier {
+            name: "a".into(),
+            span: Span::new_dud(),
+        },
     );
-    assert_eq!(
-        atom("321"),
-        Ok((
-            "",
-            AST::Number {
-                span: Span::new_dud(),
-                num: 321
-            }
-        ))
+    assert_parses(
+        atom,
+        "321",
+        "",
+        AST::Number {
+            span: Span::new_dud(),
+            num: 321,
+        },
     );
-    assert_eq!(
-        atom("(   (321))"),
-        Ok((
-            "",
-            AST::Number {
-                span: Span::new_dud(),
-                num: 321
-            }
-        ))
+    assert_parses(
+        atom,
+        "(   (321))",
+        "",
+        AST::Number {
+            span: Span::new_dud(),
+            num: 321,
+        },
     );
 }
 
+// prefix operators, which can be stacked: `!-x`, `~-x`, `**p`. a `*` here
+// is a dereference, `product` has already taken the multiplications
 #[allow(dead_code)]
-fn unary(input: &str) -> ParseResult<AST> {
-    maybe(sliteral("!"), "".into())
-        .and_then(|not| {
-            atom.map(move |term| {
-                if not[0].is_empty() {
-                    term
-                } else {
-                    AST::Not {
-                        target: Box::new(term),
-                        span: Span::new_dud(),
-                    }
-                }
-            })
-        })
-        .parse(input)
+fn unary(input: Source<'_>) -> ParseResult<'_, AST> {
+    expecting(
+        "expression",
+        // atom comes first so `-5` is a negative literal, not a negation
+        atom.or(located(
+            sliteral("!")
+                .or(sliteral("-"))
+                .or(sliteral("~"))
+                .or(single("&"))
+                .or(sliteral("*"))
+                .and_tuple(unary),
+        )
+        .map(|((operator, term), span)| match operator.as_ref() {
+            "&" => AST::AddressOf {
+                target: Box::new(term),
+                span,
+            },
+            "*" => AST::Deref {
+                target: Box::new(term),
+                span,
+            },
+            "!" => AST::Not {
+                target: Box::new(term),
+                span,
+            },
+            "~" => AST::BitNot {
+                target: Box::new(term),
+                span,
+            },
+            _ => AST::Negate {
+                target: Box::new(term),
+                span,
+            },
+        })),
+    )
+    .parse(input)
 }
 
 #[test]
 fn test_unary() {
-    assert_eq!(
-        unary("!abcd"),
-        Ok((
-            "",
-            AST::Not {
+    assert_parses(
+        unary,
+        "!abcd",
+        "",
+        AST::Not {
+            target: Box::new(AST::Identifier {
+                name: "abcd".into(),
+                span: Span::new_dud(),
+            }),
+            span: Span::new_dud(),
+        },
+    );
+
+    assert_parses(
+        unary,
+        "abcd",
+        "",
+        AST::Identifier {
+            name: "abcd".into(),
+            span: Span::new_dud(),
+        },
+    );
+
+    assert_parses(
+        unary,
+        "!-abcd",
+        "",
+        AST::Not {
+            target: Box::new(AST::Negate {
                 target: Box::new(AST::Identifier {
                     name: "abcd".into(),
-                    span: Span::new_dud()
+                    span: Span::new_dud(),
                 }),
-                span: Span::new_dud()
-            }
-        ))
+                span: Span::new_dud(),
+            }),
+            span: Span::new_dud(),
+        },
     );
 
-    assert_eq!(
-        unary("abcd"),
-        Ok((
-            "",
-            AST::Identifier {
-                name: "abcd".into(),
-                span: Span::new_dud()
-            },
-        ))
+    // a minus right before a number is part of the literal
+    assert_parses(
+        unary,
+        "-2147483648",
+        "",
+        AST::Number {
+            num: i32::MIN as i64,
+            span: Span::new_dud(),
+        },
     );
 }
 
+// `value as type`, which binds tighter than any binary operator and can be
+// chained: `x as u8 as int`
 #[allow(dead_code)]
-fn product(input: &str) -> ParseResult<AST> {
+fn cast(input: Source<'_>) -> ParseResult<'_, AST> {
     unary
-        .and_then(|left| {
-            zero_or_more(
-                sliteral("[*]")
-                    .or(sliteral("[/]"))
-                    .and_then(move |operator| {
-                        unary.and_then(move |rhs| constant((operator.clone(), rhs)))
-                    }),
+        .and_then(|value| {
+            zero_or_more(expecting("operator", keyword("as")).and_right(located(ty))).map(
+                move |types| {
+                    types
+                        .into_iter()
+                        .fold(value.clone(), |value, (ty, span)| AST::Cast {
+                            span: value.get_span().merge_with(&span),
+                            value: Box::new(value),
+                            ty,
+                        })
+                },
             )
-            .map(move |ops_and_terms| {
-                ops_and_terms
-                    .into_iter()
-                    .fold(left.clone(), |lhs, (operator, rhs)| {
-                        match operator.as_ref() {
-                            "*" => AST::Multiply {
-                                lhs: Box::new(lhs),
-                                rhs: Box::new(rhs),
-                            },
-                            "/" => AST::Divide {
-                                lhs: Box::new(lhs),
-                                rhs: Box::new(rhs),
-                            },
-                            &_ => AST::Error {
-                                span: Span::new_dud(),
-                                msg: "".into(),
-                            },
-                        }
-                    })
-            })
         })
         .parse(input)
 }
 
+#[test]
+fn test_cast() {
+    assert_parses(
+        cast,
+        "-x as u8 as int",
+        "",
+        AST::Cast {
+            span: Span::new_dud(),
+            value: Box::new(AST::Cast {
+                span: Span::new_dud(),
+                value: Box::new(AST::Negate {
+                    target: Box::new(AST::Identifier {
+                        name: "x".into(),
+                        span: Span::new_dud(),
+                    }),
+                    span: Span::new_dud(),
+                }),
+                ty: Type::U8,
+            }),
+            ty: Type::Int,
+        },
+    );
+    // a name starting with `as` is not a cast
+    assert_parses(
+        cast,
+        "x asb",
+        " asb",
+        AST::Identifier {
+            name: "x".into(),
+            span: Span::new_dud(),
+        },
+    );
+}
+
+#[allow(dead_code)]
+fn product(input: Source<'_>) -> ParseResult<'_, AST> {
+    cast.and_then(|left| {
+        zero_or_more(
+            expecting(
+                "operator",
+                sliteral("*").or(sliteral("/")).or(sliteral("%")),
+            )
+            .and_then(move |operator| cast.and_then(move |rhs| constant((operator.clone(), rhs)))),
+        )
+        .map(move |ops_and_terms| {
+            ops_and_terms
+                .into_iter()
+                .fold(left.clone(), |lhs, (operator, rhs)| {
+                    match operator.as_ref() {
+                        "*" => AST::Multiply {
+                            lhs: Box::new(lhs),
+                            rhs: Box::new(rhs),
+                        },
+                        "/" => AST::Divide {
+                            lhs: Box::new(lhs),
+                            rhs: Box::new(rhs),
+                        },
+                        "%" => AST::Modulo {
+                            lhs: Box::new(lhs),
+                            rhs: Box::new(rhs),
+                        },
+                        &_ => AST::Error {
+                            span: Span::new_dud(),
+                            msg: "".into(),
+                        },
+                    }
+                })
+        })
+    })
+    .parse(input)
+}
+
 #[test]
 fn test_product() {
-    assert_eq!(
-        product("1"),
-        Ok((
-            "",
-            AST::Number {
-                num: 1,
-                span: Span::new_dud()
-            }
-        ))
+    assert_parses(
+        product,
+        "1",
+        "",
+        AST::Number {
+            num: 1,
+            span: Span::new_dud(),
+        },
     );
-    assert_eq!(
-        product("1     *    3 /   4"),
-        Ok((
-            "",
-            AST::Divide {
-                lhs: Box::new(AST::Multiply {
-                    lhs: Box::new(AST::Number {
-                        num: 1,
-                        span: Span::new_dud()
-                    }),
-                    rhs: Box::new(AST::Number {
-                        num: 3,
-                        span: Span::new_dud()
-                    })
+    assert_parses(
+        product,
+        "1     *    3 /   4",
+        "",
+        AST::Divide {
+            lhs: Box::new(AST::Multiply {
+                lhs: Box::new(AST::Number {
+                    num: 1,
+                    span: Span::new_dud(),
                 }),
                 rhs: Box::new(AST::Number {
-                    num: 4,
-                    span: Span::new_dud()
-                })
-            }
-        ))
+                    num: 3,
+                    span: Span::new_dud(),
+                }),
+            }),
+            rhs: Box::new(AST::Number {
+                num: 4,
+                span: Span::new_dud(),
+            }),
+        },
     )
 }
 
 #[allow(dead_code)]
-fn sum(input: &str) -> ParseResult<AST> {
+fn sum(input: Source<'_>) -> ParseResult<'_, AST> {
     product
         .and_then(|left| {
             zero_or_more(
-                sliteral("[+]")
-                    .or(sliteral("[-]"))
-                    .and_then(move |operator| {
-                        product.and_then(move |rhs| constant((operator.clone(), rhs)))
-                    }),
+                expecting("operator", sliteral("+").or(sliteral("-"))).and_then(move |operator| {
+                    product.and_then(move |rhs| constant((operator.clone(), rhs)))
+                }),
             )
             .map(move |ops_and_terms| {
                 ops_and_terms
@@ -417,112 +836,133 @@ fn sum(input: &str) -> ParseResult<AST> {
 
 #[test]
 fn test_sum() {
-    assert_eq!(
-        sum("1"),
-        Ok((
-            "",
-            AST::Number {
-                num: 1,
-                span: Span::new_dud()
-            }
-        ))
+    assert_parses(
+        sum,
+        "1",
+        "",
+        AST::Number {
+            num: 1,
+            span: Span::new_dud(),
+        },
     );
-    assert_eq!(
-        sum("1     *    3 /   4"),
-        Ok((
-            "",
-            AST::Divide {
-                lhs: Box::new(AST::Multiply {
-                    lhs: Box::new(AST::Number {
-                        num: 1,
-                        span: Span::new_dud()
-                    }),
-                    rhs: Box::new(AST::Number {
-                        num: 3,
-                        span: Span::new_dud()
-                    })
+    assert_parses(
+        sum,
+        "1     *    3 /   4",
+        "",
+        AST::Divide {
+            lhs: Box::new(AST::Multiply {
+                lhs: Box::new(AST::Number {
+                    num: 1,
+                    span: Span::new_dud(),
                 }),
                 rhs: Box::new(AST::Number {
-                    num: 4,
-                    span: Span::new_dud()
-                })
-            }
-        ))
+                    num: 3,
+                    span: Span::new_dud(),
+                }),
+            }),
+            rhs: Box::new(AST::Number {
+                num: 4,
+                span: Span::new_dud(),
+            }),
+        },
     );
-    assert_eq!(
-        sum("1     +    3 -   4"),
-        Ok((
-            "",
-            AST::Subtract {
-                lhs: Box::new(AST::Add {
-                    lhs: Box::new(AST::Number {
-                        num: 1,
-                        span: Span::new_dud()
+    assert_parses(
+        sum,
+        "1     +    3 -   4",
+        "",
+        AST::Subtract {
+            lhs: Box::new(AST::Add {
+                lhs: Box::new(AST::Number {
+                    num: 1,
+                    span: Span::new_dud(),
+                }),
+                rhs: Box::new(AST::Number {
+                    num: 3,
+                    span: Span::new_dud(),
+                }),
+            }),
+            rhs: Box::new(AST::Number {
+                num: 4,
+                span: Span::new_dud(),
+            }),
+        },
+    );
+    assert_parses(
+        sum,
+        "a     *    3 /   4 + 5",
+        "",
+        AST::Add {
+            lhs: Box::new(AST::Divide {
+                lhs: Box::new(AST::Multiply {
+                    lhs: Box::new(AST::Identifier {
+                        name: "a".into(),
+                        span: Span::new_dud(),
                     }),
                     rhs: Box::new(AST::Number {
                         num: 3,
-                        span: Span::new_dud()
-                    })
+                        span: Span::new_dud(),
+                    }),
                 }),
                 rhs: Box::new(AST::Number {
                     num: 4,
-                    span: Span::new_dud()
-                })
-            }
-        ))
+                    span: Span::new_dud(),
+                }),
+            }),
+            rhs: Box::new(AST::Number {
+                num: 5,
+                span: Span::new_dud(),
+            }),
+        },
     );
-    assert_eq!(
-        sum("a     *    3 /   4 + 5"),
-        Ok((
-            "",
-            AST::Add {
-                lhs: Box::new(AST::Divide {
-                    lhs: Box::new(AST::Multiply {
-                        lhs: Box::new(AST::Identifier {
-                            name: "a".into(),
-                            span: Span::new_dud(),
-                        }),
-                        rhs: Box::new(AST::Number {
-                            num: 3,
-                            span: Span::new_dud()
-                        })
-                    }),
-                    rhs: Box::new(AST::Number {
-                        num: 4,
-                        span: Span::new_dud()
-                    })
+
+    // names stop at `-`, so this is a subtraction
+    assert_parses(
+        sum,
+        "n-1 % 3",
+        "",
+        AST::Subtract {
+            lhs: Box::new(AST::Identifier {
+                name: "n".into(),
+                span: Span::new_dud(),
+            }),
+            rhs: Box::new(AST::Modulo {
+                lhs: Box::new(AST::Number {
+                    num: 1,
+                    span: Span::new_dud(),
                 }),
                 rhs: Box::new(AST::Number {
-                    num: 5,
-                    span: Span::new_dud()
-                })
-            }
-        ))
+                    num: 3,
+                    span: Span::new_dud(),
+                }),
+            }),
+        },
     );
 }
 
 #[allow(dead_code)]
-fn comparison(input: &str) -> ParseResult<AST> {
-    sum.and_then(move |left| {
-        zero_or_more(sliteral("==").or(sliteral("!=")).and_then(move |operator| {
-            sum.and_then(move |right| constant((operator.clone(), right)))
-        }))
+fn shift(input: Source<'_>) -> ParseResult<'_, AST> {
+    sum.and_then(|left| {
+        zero_or_more(
+            expecting("operator", sliteral("<<").or(sliteral(">>"))).and_then(move |operator| {
+                sum.and_then(move |rhs| constant((operator.clone(), rhs)))
+            }),
+        )
         .map(move |ops_and_terms| {
             ops_and_terms
                 .into_iter()
                 .fold(left.clone(), |lhs, (operator, rhs)| {
                     match operator.as_ref() {
-                        "==" => AST::Equals {
+                        "<<" => AST::ShiftLeft {
                             lhs: Box::new(lhs),
                             rhs: Box::new(rhs),
                         },
-                        "!=" => AST::NEquals {
+                        ">>" => AST::ShiftRight {
                             lhs: Box::new(lhs),
                             rhs: Box::new(rhs),
                         },
                         &_ => AST::Error {
                             span: Span::new_dud(),
-                            msg: input.into(),
+                            msg: "".into(),
                         },
                     }
                 })
@@ -531,495 +971,1479 @@ fn comparison(input: &str) -> ParseResult<AST> {
     .parse(input)
 }
 
+#[allow(dead_code)]
+fn relational(input: Source<'_>) -> ParseResult<'_, AST> {
+    shift
+        .and_then(move |left| {
+            zero_or_more(
+                expecting(
+                    "operator",
+                    sliteral("<=")
+                        .or(sliteral(">="))
+                        .or(sliteral("<"))
+                        .or(sliteral(">")),
+                )
+                .and_then(move |operator| {
+                    shift.and_then(move |right| constant((operator.clone(), right)))
+                }),
+            )
+            .map(move |ops_and_terms| {
+                ops_and_terms
+                    .into_iter()
+                    .fold(left.clone(), |lhs, (operator, rhs)| {
+                        match operator.as_ref() {
+                            "<" => AST::LessThan {
+                                lhs: Box::new(lhs),
+                                rhs: Box::new(rhs),
+                            },
+                            ">" => AST::GreaterThan {
+                                lhs: Box::new(lhs),
+                                rhs: Box::new(rhs),
+                            },
+                            "<=" => AST::LessEquals {
+                                lhs: Box::new(lhs),
+                                rhs: Box::new(rhs),
+                            },
+                            ">=" => AST::GreaterEquals {
+                                lhs: Box::new(lhs),
+                                rhs: Box::new(rhs),
+                            },
+                            &_ => AST::Error {
+                                span: Span::new_dud(),
+                                msg: "".into(),
+                            },
+                        }
+                    })
+            })
+        })
+        .parse(input)
+}
+
 #[test]
-fn test_comparison() {
-    assert_eq!(
-        comparison("1"),
-        Ok((
-            "",
-            AST::Number {
-                num: 1,
-                span: Span::new_dud()
-            }
-        ))
+fn test_relational() {
+    let num = |num| {
+        Box::new(AST::Number {
+            num,
+            span: Span::new_dud(),
+        })
+    };
+    // relational operators bind tighter than `==` and looser than `+`
+    assert_parses(
+        comparison,
+        "1 + 2 < 3 == 4 >= 5",
+        "",
+        AST::Equals {
+            lhs: Box::new(AST::LessThan {
+                lhs: Box::new(AST::Add {
+                    lhs: num(1),
+                    rhs: num(2),
+                }),
+                rhs: num(3),
+            }),
+            rhs: Box::new(AST::GreaterEquals {
+                lhs: num(4),
+                rhs: num(5),
+            }),
+        },
     );
-    assert_eq!(
-        comparison("1     *    3 /   4"),
-        Ok((
-            "",
-            AST::Divide {
-                lhs: Box::new(AST::Multiply {
-                    lhs: Box::new(AST::Number {
-                        num: 1,
-                        span: Span::new_dud()
-                    }),
-                    rhs: Box::new(AST::Number {
-                        num: 3,
-                        span: Span::new_dud()
+    assert_parses(
+        relational,
+        "1 <= 2 > 3",
+        "",
+        AST::GreaterThan {
+            lhs: Box::new(AST::LessEquals {
+                lhs: num(1),
+                rhs: num(2),
+            }),
+            rhs: num(3),
+        },
+    );
+}
+
+#[allow(dead_code)]
+fn comparison(input: Source<'_>) -> ParseResult<'_, AST> {
+    relational
+        .and_then(move |left| {
+            zero_or_more(
+                expecting("operator", sliteral("==").or(sliteral("!="))).and_then(
+                    move |operator| {
+                        relational.and_then(move |right| constant((operator.clone(), right)))
+                    },
+                ),
+            )
+            .map(move |ops_and_terms| {
+                ops_and_terms
+                    .into_iter()
+                    .fold(left.clone(), |lhs, (operator, rhs)| {
+                        match operator.as_ref() {
+                            "==" => AST::Equals {
+                                lhs: Box::new(lhs),
+                                rhs: Box::new(rhs),
+                            },
+                            "!=" => AST::NEquals {
+                                lhs: Box::new(lhs),
+                                rhs: Box::new(rhs),
+                            },
+                            &_ => AST::Error {
+                                span: Span::new_dud(),
+                                msg: "".into(),
+                            },
+                        }
                     })
-                }),
-                rhs: Box::new(AST::Number {
-                    num: 4,
-                    span: Span::new_dud()
-                })
-            }
-        ))
+            })
+        })
+        .parse(input)
+}
+
+#[test]
+fn test_comparison() {
+    assert_parses(
+        comparison,
+        "1",
+        "",
+        AST::Number {
+            num: 1,
+            span: Span::new_dud(),
+        },
     );
-    assert_eq!(
-        comparison("1 != 2"),
-        Ok((
-            "",
-            AST::NEquals {
+    assert_parses(
+        comparison,
+        "1     *    3 /   4",
+        "",
+        AST::Divide {
+            lhs: Box::new(AST::Multiply {
                 lhs: Box::new(AST::Number {
                     num: 1,
-                    span: Span::new_dud()
+                    span: Span::new_dud(),
                 }),
                 rhs: Box::new(AST::Number {
-                    num: 2,
-                    span: Span::new_dud()
-                })
-            }
-        ))
+                    num: 3,
+                    span: Span::new_dud(),
+                }),
+            }),
+            rhs: Box::new(AST::Number {
+                num: 4,
+                span: Span::new_dud(),
+            }),
+        },
     );
-    assert_eq!(
-        comparison("1     +    3 -   4"),
-        Ok((
-            "",
-            AST::Subtract {
-                lhs: Box::new(AST::Add {
+    assert_parses(
+        comparison,
+        "1 != 2",
+        "",
+        AST::NEquals {
+            lhs: Box::new(AST::Number {
+                num: 1,
+                span: Span::new_dud(),
+            }),
+            rhs: Box::new(AST::Number {
+                num: 2,
+                span: Span::new_dud(),
+            }),
+        },
+    );
+    assert_parses(
+        comparison,
+        "1     +    3 -   4",
+        "",
+        AST::Subtract {
+            lhs: Box::new(AST::Add {
+                lhs: Box::new(AST::Number {
+                    num: 1,
+                    span: Span::new_dud(),
+                }),
+                rhs: Box::new(AST::Number {
+                    num: 3,
+                    span: Span::new_dud(),
+                }),
+            }),
+            rhs: Box::new(AST::Number {
+                num: 4,
+                span: Span::new_dud(),
+            }),
+        },
+    );
+    assert_parses(
+        comparison,
+        "1     *    3 /   4 + 5",
+        "",
+        AST::Add {
+            lhs: Box::new(AST::Divide {
+                lhs: Box::new(AST::Multiply {
                     lhs: Box::new(AST::Number {
                         num: 1,
-                        span: Span::new_dud()
+                        span: Span::new_dud(),
                     }),
                     rhs: Box::new(AST::Number {
                         num: 3,
-                        span: Span::new_dud()
-                    })
+                        span: Span::new_dud(),
+                    }),
                 }),
                 rhs: Box::new(AST::Number {
                     num: 4,
-                    span: Span::new_dud()
-                })
-            }
-        ))
+                    span: Span::new_dud(),
+                }),
+            }),
+            rhs: Box::new(AST::Number {
+                num: 5,
+                span: Span::new_dud(),
+            }),
+        },
     );
-    assert_eq!(
-        comparison("1     *    3 /   4 + 5"),
-        Ok((
-            "",
-            AST::Add {
-                lhs: Box::new(AST::Divide {
-                    lhs: Box::new(AST::Multiply {
-                        lhs: Box::new(AST::Number {
-                            num: 1,
-                            span: Span::new_dud()
-                        }),
-                        rhs: Box::new(AST::Number {
-                            num: 3,
-                            span: Span::new_dud()
-                        })
+    assert_parses(
+        comparison,
+        "a + 1 == b - 1 != c",
+        "",
+        AST::NEquals {
+            lhs: Box::new(AST::Equals {
+                lhs: Box::new(AST::Add {
+                    lhs: Box::new(AST::Identifier {
+                        name: "a".into(),
+                        span: Span::new_dud(),
                     }),
                     rhs: Box::new(AST::Number {
-                        num: 4,
-                        span: Span::new_dud()
-                    })
+                        num: 1,
+                        span: Span::new_dud(),
+                    }),
                 }),
-                rhs: Box::new(AST::Number {
-                    num: 5,
-                    span: Span::new_dud()
-                })
-            }
-        ))
+                rhs: Box::new(AST::Subtract {
+                    lhs: Box::new(AST::Identifier {
+                        name: "b".into(),
+                        span: Span::new_dud(),
+                    }),
+                    rhs: Box::new(AST::Number {
+                        num: 1,
+                        span: Span::new_dud(),
+                    }),
+                }),
+            }),
+            rhs: Box::new(AST::Identifier {
+                name: "c".into(),
+                span: Span::new_dud(),
+            }),
+        },
     );
-    assert_eq!(
-        comparison("a + 1 == b - 1 != c"),
-        Ok((
-            "",
-            AST::NEquals {
-                lhs: Box::new(AST::Equals {
-                    lhs: Box::new(AST::Add {
-                        lhs: Box::new(AST::Identifier {
-                            name: "a".into(),
-                            span: Span::new_dud()
-                        }),
-                        rhs: Box::new(AST::Number {
-                            num: 1,
-                            span: Span::new_dud()
+}
+
+// `op`, but not when it is the first half of the doubled operator: `&`
+// and not `&&`
+#[allow(dead_code)]
+fn single<'a>(op: &'a str) -> impl Parser<'a, String> {
+    move |input: Source<'a>| {
+        let (start, _, _) = ignored.parse(input)?;
+        let (next, res, hint) = sliteral(op).parse(start)?;
+        if next.rest().starts_with(op) {
+            return Err(ParseError::n(start, &format!("`{op}`")));
+        }
+        Ok((next, res, hint))
+    }
+}
+
+#[allow(dead_code)]
+fn bit_and(input: Source<'_>) -> ParseResult<'_, AST> {
+    comparison
+        .and_then(|left| {
+            zero_or_more(expecting("operator", single("&")).and_right(comparison)).map(
+                move |terms| {
+                    terms
+                        .into_iter()
+                        .fold(left.clone(), |lhs, rhs| AST::BitAnd {
+                            lhs: Box::new(lhs),
+                            rhs: Box::new(rhs),
                         })
-                    }),
-                    rhs: Box::new(AST::Subtract {
-                        lhs: Box::new(AST::Identifier {
-                            name: "b".into(),
-                            span: Span::new_dud()
-                        }),
-                        rhs: Box::new(AST::Number {
-                            num: 1,
-                            span: Span::new_dud()
+                },
+            )
+        })
+        .parse(input)
+}
+
+#[allow(dead_code)]
+fn bit_xor(input: Source<'_>) -> ParseResult<'_, AST> {
+    bit_and
+        .and_then(|left| {
+            zero_or_more(expecting("operator", sliteral("^")).and_right(bit_and)).map(
+                move |terms| {
+                    terms
+                        .into_iter()
+                        .fold(left.clone(), |lhs, rhs| AST::BitXor {
+                            lhs: Box::new(lhs),
+                            rhs: Box::new(rhs),
                         })
+                },
+            )
+        })
+        .parse(input)
+}
+
+#[allow(dead_code)]
+fn bit_or(input: Source<'_>) -> ParseResult<'_, AST> {
+    bit_xor
+        .and_then(|left| {
+            zero_or_more(expecting("operator", single("|")).and_right(bit_xor)).map(move |terms| {
+                terms.into_iter().fold(left.clone(), |lhs, rhs| AST::BitOr {
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(rhs),
+                })
+            })
+        })
+        .parse(input)
+}
+
+#[test]
+fn test_bitwise() {
+    let num = |num| {
+        Box::new(AST::Number {
+            num,
+            span: Span::new_dud(),
+        })
+    };
+    // C's ladder: shifts under `+`, then comparisons, then `&`, `^`, `|`
+    assert_parses(
+        bit_or,
+        "1 | 2 ^ 3 & 4 == 5 << 1 + 1",
+        "",
+        AST::BitOr {
+            lhs: num(1),
+            rhs: Box::new(AST::BitXor {
+                lhs: num(2),
+                rhs: Box::new(AST::BitAnd {
+                    lhs: num(3),
+                    rhs: Box::new(AST::Equals {
+                        lhs: num(4),
+                        rhs: Box::new(AST::ShiftLeft {
+                            lhs: num(5),
+                            rhs: Box::new(AST::Add {
+                                lhs: num(1),
+                                rhs: num(1),
+                            }),
+                        }),
                     }),
                 }),
-                rhs: Box::new(AST::Identifier {
-                    name: "c".into(),
-                    span: Span::new_dud()
-                })
-            }
-        ))
+            }),
+        },
+    );
+    // `&&` and `||` are left for the logical operators
+    assert_parses(
+        logical_or,
+        "1 & 2 && 3 >> 1 || ~4",
+        "",
+        AST::Or {
+            lhs: Box::new(AST::And {
+                lhs: Box::new(AST::BitAnd {
+                    lhs: num(1),
+                    rhs: num(2),
+                }),
+                rhs: Box::new(AST::ShiftRight {
+                    lhs: num(3),
+                    rhs: num(1),
+                }),
+            }),
+            rhs: Box::new(AST::BitNot {
+                target: num(4),
+                span: Span::new_dud(),
+            }),
+        },
     );
 }
 
 #[allow(dead_code)]
-fn statement(input: &str) -> ParseResult<AST> {
-    return_s
-        .or(fn_s)
-        .or(if_s)
-        .or(while_s)
-        .or(var_s)
-        .or(assign_s)
-        .or(block_s)
-        .or(expr_s)
+fn logical_and(input: Source<'_>) -> ParseResult<'_, AST> {
+    bit_or
+        .and_then(|left| {
+            zero_or_more(expecting("operator", sliteral("&&")).and_right(bit_or)).map(
+                move |terms| {
+                    terms.into_iter().fold(left.clone(), |lhs, rhs| AST::And {
+                        lhs: Box::new(lhs),
+                        rhs: Box::new(rhs),
+                    })
+                },
+            )
+        })
         .parse(input)
 }
 
 #[allow(dead_code)]
-fn return_s(input: &str) -> ParseResult<AST> {
-    sliteral("ret")
-        .and_right(expression)
-        .and_then(|val| {
-            sliteral("[;]").and_right(constant(AST::Return {
-                value: Box::new(val),
-                span: Span::new_dud(),
-            }))
+fn logical_or(input: Source<'_>) -> ParseResult<'_, AST> {
+    logical_and
+        .and_then(|left| {
+            zero_or_more(expecting("operator", sliteral("||")).and_right(logical_and)).map(
+                move |terms| {
+                    terms.into_iter().fold(left.clone(), |lhs, rhs| AST::Or {
+                        lhs: Box::new(lhs),
+                        rhs: Box::new(rhs),
+                    })
+                },
+            )
         })
         .parse(input)
 }
 
+#[test]
+fn test_logical() {
+    let id = |name: &str| {
+        Box::new(AST::Identifier {
+            name: name.into(),
+            span: Span::new_dud(),
+        })
+    };
+    // `&&` binds tighter than `||`, and both looser than comparisons
+    assert_parses(
+        logical_or,
+        "a || b && c == d || !e",
+        "",
+        AST::Or {
+            lhs: Box::new(AST::Or {
+                lhs: id("a"),
+                rhs: Box::new(AST::And {
+                    lhs: id("b"),
+                    rhs: Box::new(AST::Equals {
+                        lhs: id("c"),
+                        rhs: id("d"),
+                    }),
+                }),
+            }),
+            rhs: Box::new(AST::Not {
+                target: id("e"),
+                span: Span::new_dud(),
+            }),
+        },
+    );
+}
+
+#[allow(dead_code)]
+fn statement(input: Source<'_>) -> ParseResult<'_, AST> {
+    expecting(
+        "statement",
+        return_s
+            .or(break_s)
+            .or(continue_s)
+            .or(fn_s)
+            .or(struct_s)
+            .or(if_s)
+            .or(while_s)
+            .or(for_s)
+            .or(var_s)
+            .or(global_s)
+            .or(const_s)
+            .or(extern_s)
+            .or(assign_s)
+            .or(index_assign_s)
+            .or(deref_assign_s)
+            .or(field_assign_s)
+            .or(block_s)
+            .or(expr_s),
+    )
+    .parse(input)
+}
+
+// the AST::Error standing in for input that failed with `err`. it points at
+// the token that was not expected
+pub fn error_node(err: &ParseError<'_>) -> AST {
+    let (found, end) = match token.parse(err.at) {
+        Ok((end, tok, _)) => (format!("`{tok}`"), end),
+        Err(_) => ("end of input".into(), err.at),
+    };
+    AST::Error {
+        span: err.at.span_to(&end),
+        msg: format!("{err}, found {found}"),
+    }
+}
+
+// skips the rest of a statement that failed to parse: up to and including
+// the next `;`, or up to the `}` closing the enclosing block. braces opened
+// on the way are skipped as a whole, so a broken `while` or function goes
+// in one piece, and a `;` inside brackets, like that of `[u8; 4]`, does
+// not end the statement
+fn skip_statement(input: Source<'_>) -> Source<'_> {
+    let mut depth = 0;
+    let mut brackets = 0;
+    let mut at = input;
+    while let Ok((next, tok, _)) = ignored.and_right(token).parse(at) {
+        match tok.as_str() {
+            "[" | "(" => brackets += 1,
+            "]" | ")" => brackets = (brackets - 1).max(0),
+            ";" if depth == 0 && brackets == 0 => return next,
+            "}" if depth == 0 => return at,
+            "}" if depth == 1 => return next,
+            "{" => depth += 1,
+            "}" => depth -= 1,
+            _ => {}
+        }
+        at = next;
+    }
+    at
+}
+
+// a statement, or an AST::Error in its place so parsing can carry on and
+// report the mistakes after it too. fails only where there is nothing to
+// skip: at the end of the input, or (inside a block) at its closing `}`
+fn statement_or_error<'a>(top_level: bool) -> impl Parser<'a, AST> {
+    move |input: Source<'a>| match statement.parse(input) {
+        Ok(res) => Ok(res),
+        Err(err) => {
+            let (start, _, _) = ignored.parse(input)?;
+            let mut next = skip_statement(start);
+            if next == start {
+                if !top_level || start.rest().is_empty() {
+                    return Err(err);
+                }
+                // a stray `}` at the top level
+                (next, _, _) = token.parse(start)?;
+            }
+            Ok((next, error_node(&err), None))
+        }
+    }
+}
+
+#[test]
+fn test_statement_or_error() {
+    let (rest, ast) = parse_str(&statement_or_error(false), " mut x = ; ret 1;").unwrap();
+    assert_eq!(rest, " ret 1;");
+    assert!(matches!(ast, AST::Error { .. }));
+    let (rest, _) = parse_str(&statement_or_error(false), "mut x: [u8; 0] = 1; y;").unwrap();
+    assert_eq!(rest, " y;");
+
+    // the block of a broken statement is skipped along with it
+    let (rest, _) = parse_str(&statement_or_error(false), "while { x; } y;").unwrap();
+    assert_eq!(rest, " y;");
+
+    // the `}` belongs to the enclosing block
+    let (rest, _) = parse_str(&statement_or_error(false), "x = 1 }").unwrap();
+    assert_eq!(rest, " }");
+    assert_eq!(parse_str(&statement_or_error(false), " }"), Err("}"));
+    let (rest, _) = parse_str(&statement_or_error(true), " } ret 1;").unwrap();
+    assert_eq!(rest, " ret 1;");
+}
+
+#[allow(dead_code)]
+fn return_s(input: Source<'_>) -> ParseResult<'_, AST> {
+    located(
+        keyword("ret")
+            .and_right(expecting("expression after `ret`", expression))
+            .and_left(sliteral(";")),
+    )
+    .map(|(val, span)| AST::Return {
+        value: Box::new(val),
+        span,
+    })
+    .parse(input)
+}
+
 #[test]
 fn test_return_s() {
-    assert_eq!(
-        return_s("    ret a;"),
-        Ok((
-            "",
-            AST::Return {
+    assert_parses(
+        return_s,
+        "    ret a;",
+        "",
+        AST::Return {
+            value: Box::new(AST::Identifier {
+                name: "a".into(),
+                span: Span::new_dud(),
+            }),
+            span: Span::new_dud(),
+        },
+    );
+}
+
+#[allow(dead_code)]
+fn expr_s(input: Source<'_>) -> ParseResult<'_, AST> {
+    expression
+        .and_then(|expr| sliteral(";").and_right(constant(expr)))
+        .parse(input)
+}
+
+#[test]
+fn test_expr_s() {
+    assert_parses(
+        expr_s,
+        "   1   ;",
+        "",
+        AST::Number {
+            num: 1,
+            span: Span::new_dud(),
+        },
+    )
+}
+
+// `else` is optional, and since it takes any statement, `else if` chains
+// come out as nested IfConds
+#[allow(dead_code)]
+fn if_s(input: Source<'_>) -> ParseResult<'_, AST> {
+    located(
+        keyword("if")
+            .and_right(expecting("condition after `if`", expression))
+            .and_then(|conditional| {
+                statement.and_then(closure!(clone conditional, |then_body| {
+                    maybe(keyword("else").and_right(statement).map(Some), None).map(
+                        closure!(clone conditional, clone then_body, |else_body| {
+                            (conditional.clone(), then_body.clone(), else_body[0].clone())
+                        }),
+                    )
+                }))
+            }),
+    )
+    .map(|((conditional, then_body, else_body), span)| AST::IfCond {
+        span,
+        condition: Box::new(conditional),
+        then: Box::new(then_body),
+        c_else: else_body.map(Box::new),
+    })
+    .parse(input)
+}
+
+#[test]
+fn test_if_s() {
+    assert_parses(
+        if_s,
+        "if a == b ret a; else ret b;",
+        "",
+        AST::IfCond {
+            span: Span::new_dud(),
+            condition: Box::new(AST::Equals {
+                lhs: Box::new(AST::Identifier {
+                    name: "a".into(),
+                    span: Span::new_dud(),
+                }),
+                rhs: Box::new(AST::Identifier {
+                    name: "b".into(),
+                    span: Span::new_dud(),
+                }),
+            }),
+            then: Box::new(AST::Return {
                 value: Box::new(AST::Identifier {
                     name: "a".into(),
-                    span: Span::new_dud()
+                    span: Span::new_dud(),
                 }),
-                span: Span::new_dud()
-            }
-        ))
+                span: Span::new_dud(),
+            }),
+            c_else: Some(Box::new(AST::Return {
+                value: Box::new(AST::Identifier {
+                    name: "b".into(),
+                    span: Span::new_dud(),
+                }),
+                span: Span::new_dud(),
+            })),
+        },
+    );
+
+    let id = |name: &str| {
+        Box::new(AST::Identifier {
+            name: name.into(),
+            span: Span::new_dud(),
+        })
+    };
+    let ret = |name: &str| {
+        Box::new(AST::Return {
+            value: id(name),
+            span: Span::new_dud(),
+        })
+    };
+    // `else` is optional, and `elsewhere` is not an else
+    assert_parses(
+        if_s,
+        "if a ret a; elsewhere = 1;",
+        " elsewhere = 1;",
+        AST::IfCond {
+            span: Span::new_dud(),
+            condition: id("a"),
+            then: ret("a"),
+            c_else: None,
+        },
+    );
+    assert_parses(
+        if_s,
+        "if a ret a; else if b ret b; else ret c;",
+        "",
+        AST::IfCond {
+            span: Span::new_dud(),
+            condition: id("a"),
+            then: ret("a"),
+            c_else: Some(Box::new(AST::IfCond {
+                span: Span::new_dud(),
+                condition: id("b"),
+                then: ret("b"),
+                c_else: Some(ret("c")),
+            })),
+        },
+    );
+}
+
+#[allow(dead_code)]
+fn while_s(input: Source<'_>) -> ParseResult<'_, AST> {
+    located(
+        keyword("while")
+            .and_right(expecting("condition after `while`", expression))
+            .and_then(|conditional| {
+                statement.and_then(move |body| constant((conditional.clone(), body)))
+            }),
+    )
+    .map(|((conditional, body), span)| AST::WhileLoop {
+        span,
+        condition: Box::new(conditional),
+        body: Box::new(body),
+        step: None,
+    })
+    .parse(input)
+}
+
+#[test]
+fn test_while_s() {
+    assert_parses(
+        while_s,
+        "while a == b ret a;",
+        "",
+        AST::WhileLoop {
+            span: Span::new_dud(),
+            step: None,
+            condition: Box::new(AST::Equals {
+                lhs: Box::new(AST::Identifier {
+                    name: "a".into(),
+                    span: Span::new_dud(),
+                }),
+                rhs: Box::new(AST::Identifier {
+                    name: "b".into(),
+                    span: Span::new_dud(),
+                }),
+            }),
+            body: Box::new(AST::Return {
+                value: Box::new(AST::Identifier {
+                    name: "a".into(),
+                    span: Span::new_dud(),
+                }),
+                span: Span::new_dud(),
+            }),
+        },
+    )
+}
+
+// what a `for` header turns into: statements to run first, the condition
+// and the step
+type ForHeader = (Vec<AST>, AST, AST);
+
+// `for i in start..end`. the end is worked out once, into a hidden local
+// no sac name can clash with
+#[allow(dead_code)]
+fn range_header(input: Source<'_>) -> ParseResult<'_, ForHeader> {
+    located(sidentifier)
+        .and_left(keyword("in"))
+        .and_tuple(expecting("start of range", expression))
+        .and_left(sliteral(".."))
+        .and_tuple(expecting("end of range", expression))
+        .map(|(((var, name), start), end)| {
+            // what is made up here is written where `i` or the end are
+            let end_var = format!("{var}.end");
+            let end_span = end.get_span();
+            let id = |name: &str, span: &Span| {
+                Box::new(AST::Identifier {
+                    name: name.into(),
+                    span: span.clone(),
+                })
+            };
+            let init = vec![
+                AST::Variable {
+                    span: name.clone(),
+                    name: var.clone(),
+                    ty: None,
+                    value: Box::new(start),
+                },
+                AST::Variable {
+                    span: end_span.clone(),
+                    name: end_var.clone(),
+                    ty: None,
+                    value: Box::new(end),
+                },
+            ];
+            let condition = AST::LessThan {
+                lhs: id(&var, &name),
+                rhs: id(&end_var, &end_span),
+            };
+            let step = AST::Assignment {
+                span: name.clone(),
+                name: var.clone(),
+                value: Box::new(AST::Add {
+                    lhs: id(&var, &name),
+                    rhs: Box::new(AST::Number {
+                        num: 1,
+                        span: name.clone(),
+                    }),
+                }),
+            };
+            (init, condition, step)
+        })
+        .parse(input)
+}
+
+// `for mut i = 0; i < n; i = i + 1`
+#[allow(dead_code)]
+fn c_header(input: Source<'_>) -> ParseResult<'_, ForHeader> {
+    var_s
+        .and_tuple(expecting("condition", expression))
+        .and_left(sliteral(";"))
+        .and_tuple(expecting("step", assignment.or(expression)))
+        .map(|((init, condition), step)| (vec![init], condition, step))
+        .parse(input)
+}
+
+// both kinds of `for` become a while loop in a block of its own, so the loop
+// variable is gone once the loop is over
+#[allow(dead_code)]
+fn for_s(input: Source<'_>) -> ParseResult<'_, AST> {
+    located(
+        keyword("for")
+            .and_right(range_header.or(c_header))
+            .and_tuple(expecting("loop body", block_s)),
+    )
+    .map(|(((mut init, condition, step), body), span)| {
+        init.push(AST::WhileLoop {
+            span: span.clone(),
+            condition: Box::new(condition),
+            body: Box::new(body),
+            step: Some(Box::new(step)),
+        });
+        AST::Block {
+            statements: init,
+            span,
+        }
+    })
+    .parse(input)
+}
+
+#[test]
+fn test_for_s() {
+    let id = |name: &str| {
+        Box::new(AST::Identifier {
+            name: name.into(),
+            span: Span::new_dud(),
+        })
+    };
+    let num = |num| {
+        Box::new(AST::Number {
+            num,
+            span: Span::new_dud(),
+        })
+    };
+    let increment = AST::Assignment {
+        span: Span::new_dud(),
+        name: "i".into(),
+        value: Box::new(AST::Add {
+            lhs: id("i"),
+            rhs: num(1),
+        }),
+    };
+    let body = AST::Block {
+        statements: vec![AST::Call {
+            called: "f".into(),
+            args: vec![*id("i")],
+            span: Span::new_dud(),
+        }],
+        span: Span::new_dud(),
+    };
+
+    assert_parses(
+        for_s,
+        "for mut i = 0; i < n; i = i + 1 { f(i); }",
+        "",
+        AST::Block {
+            statements: vec![
+                AST::Variable {
+                    span: Span::new_dud(),
+                    name: "i".into(),
+                    ty: None,
+                    value: num(0),
+                },
+                AST::WhileLoop {
+                    span: Span::new_dud(),
+                    condition: Box::new(AST::LessThan {
+                        lhs: id("i"),
+                        rhs: id("n"),
+                    }),
+                    body: Box::new(body.clone()),
+                    step: Some(Box::new(increment.clone())),
+                },
+            ],
+            span: Span::new_dud(),
+        },
     );
+
+    assert_parses(
+        for_s,
+        "for i in 0..n * 2 { f(i); }",
+        "",
+        AST::Block {
+            statements: vec![
+                AST::Variable {
+                    span: Span::new_dud(),
+                    name: "i".into(),
+                    ty: None,
+                    value: num(0),
+                },
+                AST::Variable {
+                    span: Span::new_dud(),
+                    name: "i.end".into(),
+                    ty: None,
+                    value: Box::new(AST::Multiply {
+                        lhs: id("n"),
+                        rhs: num(2),
+                    }),
+                },
+                AST::WhileLoop {
+                    span: Span::new_dud(),
+                    condition: Box::new(AST::LessThan {
+                        lhs: id("i"),
+                        rhs: id("i.end"),
+                    }),
+                    body: Box::new(body),
+                    step: Some(Box::new(increment)),
+                },
+            ],
+            span: Span::new_dud(),
+        },
+    );
+}
+
+#[allow(dead_code)]
+fn break_s(input: Source<'_>) -> ParseResult<'_, AST> {
+    located(keyword("break").and_left(sliteral(";")))
+        .map(|(_, span)| AST::Break { span })
+        .parse(input)
+}
+
+#[allow(dead_code)]
+fn continue_s(input: Source<'_>) -> ParseResult<'_, AST> {
+    located(keyword("continue").and_left(sliteral(";")))
+        .map(|(_, span)| AST::Continue { span })
+        .parse(input)
+}
+
+#[test]
+fn test_break_and_continue() {
+    assert_parses(
+        while_s,
+        "while 1 { if a break; continue; }",
+        "",
+        AST::WhileLoop {
+            span: Span::new_dud(),
+            step: None,
+            condition: Box::new(AST::Number {
+                num: 1,
+                span: Span::new_dud(),
+            }),
+            body: Box::new(AST::Block {
+                statements: vec![
+                    AST::IfCond {
+                        span: Span::new_dud(),
+                        condition: Box::new(AST::Identifier {
+                            name: "a".into(),
+                            span: Span::new_dud(),
+                        }),
+                        then: Box::new(AST::Break {
+                            span: Span::new_dud(),
+                        }),
+                        c_else: None,
+                    },
+                    AST::Continue {
+                        span: Span::new_dud(),
+                    },
+                ],
+                span: Span::new_dud(),
+            }),
+        },
+    );
+    // a name that only starts with the keyword is still a name
+    assert_parses(
+        statement,
+        "breaks;",
+        "",
+        AST::Identifier {
+            name: "breaks".into(),
+            span: Span::new_dud(),
+        },
+    );
+}
+
+// `kw name = value;`, the shape of every declaration
+fn declaration<'a, P, N>(kw: &'a str, name: P) -> impl Parser<'a, ((N, AST), Span)>
+where
+    P: Parser<'a, N> + 'a,
+    N: Clone + 'a,
+{
+    located(keyword(kw).and_right(name).and_then(|var_name| {
+        sliteral("=")
+            .and_right(expecting("expression after `=`", struct_lit.or(expression)))
+            .and_then(move |value| sliteral(";").and_right(constant((var_name.clone(), value))))
+    }))
+}
+
+#[allow(dead_code)]
+fn var_s(input: Source<'_>) -> ParseResult<'_, AST> {
+    let name = expecting("variable name after `mut`", sidentifier).and_tuple(annotation);
+    declaration("mut", name)
+        .map(|(((var_name, ty), value), span)| AST::Variable {
+            span,
+            name: var_name,
+            ty,
+            value: Box::new(value),
+        })
+        .parse(input)
+}
+
+#[allow(dead_code)]
+fn global_s(input: Source<'_>) -> ParseResult<'_, AST> {
+    declaration(
+        "global",
+        expecting("variable name after `global`", sidentifier),
+    )
+    .map(|((name, value), span)| AST::Global {
+        span,
+        name,
+        value: Box::new(value),
+    })
+    .parse(input)
+}
+
+#[allow(dead_code)]
+fn const_s(input: Source<'_>) -> ParseResult<'_, AST> {
+    declaration(
+        "const",
+        expecting("constant name after `const`", sidentifier),
+    )
+    .map(|((name, value), span)| AST::Const {
+        span,
+        name,
+        value: Box::new(value),
+    })
+    .parse(input)
+}
+
+// `extern strlen;`
+#[allow(dead_code)]
+fn extern_s(input: Source<'_>) -> ParseResult<'_, AST> {
+    located(
+        keyword("extern")
+            .and_right(expecting("function name after `extern`", sidentifier))
+            .and_left(expecting("`;`", sliteral(";"))),
+    )
+    .map(|(name, span)| AST::Extern { span, name })
+    .parse(input)
+}
+
+#[test]
+fn test_global_and_const_s() {
+    assert_parses(
+        global_s,
+        "global count = 0;",
+        "",
+        AST::Global {
+            span: Span::new_dud(),
+            name: "count".into(),
+            value: Box::new(AST::Number {
+                num: 0,
+                span: Span::new_dud(),
+            }),
+        },
+    );
+    assert_parses(
+        statement,
+        "const N = 10;",
+        "",
+        AST::Const {
+            span: Span::new_dud(),
+            name: "N".into(),
+            value: Box::new(AST::Number {
+                num: 10,
+                span: Span::new_dud(),
+            }),
+        },
+    );
+    assert_parses(
+        statement,
+        "extern strlen;",
+        "",
+        AST::Extern {
+            span: Span::new_dud(),
+            name: "strlen".into(),
+        },
+    );
+}
+
+#[test]
+fn test_var_s() {
+    assert_parses(
+        var_s,
+        "mut a = c;",
+        "",
+        AST::Variable {
+            span: Span::new_dud(),
+            name: "a".into(),
+            ty: None,
+            value: Box::new(AST::Identifier {
+                name: "c".into(),
+                span: Span::new_dud(),
+            }),
+        },
+    );
+}
+
+// `name = value` without the `;`, which `for` headers need
+#[allow(dead_code)]
+fn assignment(input: Source<'_>) -> ParseResult<'_, AST> {
+    located(sidentifier.and_then(|var_name| {
+        sliteral("=")
+            .and_right(expecting("expression after `=`", expression))
+            .map(move |val| (var_name.clone(), val))
+    }))
+    .map(|((var_name, val), span)| AST::Assignment {
+        span,
+        name: var_name,
+        value: Box::new(val),
+    })
+    .parse(input)
+}
+
+#[allow(dead_code)]
+fn assign_s(input: Source<'_>) -> ParseResult<'_, AST> {
+    assignment.and_left(sliteral(";")).parse(input)
+}
+
+// `name.field = value;`
+#[allow(dead_code)]
+fn field_assign_s(input: Source<'_>) -> ParseResult<'_, AST> {
+    located(dotted.and_then(|((name, field), _)| {
+        sliteral("=")
+            .and_right(expecting("expression after `=`", expression))
+            .and_then(move |value| {
+                sliteral(";").and_right(constant((name.clone(), field.clone(), value)))
+            })
+    }))
+    .map(|((name, field, value), span)| AST::FieldAssign {
+        span,
+        name,
+        field,
+        value: Box::new(value),
+    })
+    .parse(input)
 }
 
+// `*pointer = value;`
 #[allow(dead_code)]
-fn expr_s(input: &str) -> ParseResult<AST> {
-    expression
-        .and_then(|expr| sliteral("[;]").and_right(constant(expr)))
-        .parse(input)
+fn deref_assign_s(input: Source<'_>) -> ParseResult<'_, AST> {
+    located(sliteral("*").and_right(unary).and_then(|target| {
+        sliteral("=")
+            .and_right(expecting("expression after `=`", expression))
+            .and_then(move |value| sliteral(";").and_right(constant((target.clone(), value))))
+    }))
+    .map(|((target, value), span)| AST::DerefAssign {
+        span,
+        target: Box::new(target),
+        value: Box::new(value),
+    })
+    .parse(input)
 }
 
 #[test]
-fn test_expr_s() {
-    assert_eq!(
-        expr_s("   1   ;"),
-        Ok((
-            "",
-            AST::Number {
-                num: 1,
-                span: Span::new_dud()
-            }
-        ))
-    )
+fn test_pointers() {
+    let id = |name: &str| {
+        Box::new(AST::Identifier {
+            name: name.into(),
+            span: Span::new_dud(),
+        })
+    };
+    // `*` between operands multiplies, in front of one it dereferences
+    assert_parses(
+        expression,
+        "a * *p & &b",
+        "",
+        AST::BitAnd {
+            lhs: Box::new(AST::Multiply {
+                lhs: id("a"),
+                rhs: Box::new(AST::Deref {
+                    target: id("p"),
+                    span: Span::new_dud(),
+                }),
+            }),
+            rhs: Box::new(AST::AddressOf {
+                target: id("b"),
+                span: Span::new_dud(),
+            }),
+        },
+    );
+    assert_parses(
+        statement,
+        "**pp = *p;",
+        "",
+        AST::DerefAssign {
+            span: Span::new_dud(),
+            target: Box::new(AST::Deref {
+                target: id("pp"),
+                span: Span::new_dud(),
+            }),
+            value: Box::new(AST::Deref {
+                target: id("p"),
+                span: Span::new_dud(),
+            }),
+        },
+    );
 }
 
+// `name[index] = value;`
 #[allow(dead_code)]
-fn if_s(input: &str) -> ParseResult<AST> {
-    sliteral("if")
-        .and_right(expression)
-        .and_then(|conditional| {
-            statement.and_then(closure!(clone conditional, |then_body| {
-                sliteral("else")
-                    .and_right(statement)
-                    .and_then(closure!(clone conditional, clone then_body, |else_body| {
-                        constant(AST::IfCond {
-                            span: Span::new_dud(),
-                            condition: Box::new(conditional.clone()),
-                            then: Box::new(then_body.clone()),
-                            c_else: Box::new(else_body),
-                        })
-                    }))
-            }))
-        })
-        .parse(input)
+fn index_assign_s(input: Source<'_>) -> ParseResult<'_, AST> {
+    located(indexed.and_then(|((name, index), _)| {
+        sliteral("=")
+            .and_right(expecting("expression after `=`", expression))
+            .and_then(move |value| {
+                sliteral(";").and_right(constant((name.clone(), index.clone(), value)))
+            })
+    }))
+    .map(|((name, index, value), span)| AST::IndexAssign {
+        span,
+        name,
+        index: Box::new(index),
+        value: Box::new(value),
+    })
+    .parse(input)
 }
 
 #[test]
-fn test_if_s() {
-    assert_eq!(
-        if_s("if a == b ret a; else ret b;"),
-        Ok((
-            "",
-            AST::IfCond {
+fn test_assign_s() {
+    assert_parses(
+        assign_s,
+        "a = 300;",
+        "",
+        AST::Assignment {
+            span: Span::new_dud(),
+            name: "a".into(),
+            value: Box::new(AST::Number {
+                num: 300,
                 span: Span::new_dud(),
-                condition: Box::new(AST::Equals {
-                    lhs: Box::new(AST::Identifier {
-                        name: "a".into(),
-                        span: Span::new_dud()
-                    }),
-                    rhs: Box::new(AST::Identifier {
-                        name: "b".into(),
-                        span: Span::new_dud()
-                    })
-                }),
-                then: Box::new(AST::Return {
-                    value: Box::new(AST::Identifier {
-                        name: "a".into(),
-                        span: Span::new_dud()
-                    }),
-                    span: Span::new_dud()
-                }),
-                c_else: Box::new(AST::Return {
-                    value: Box::new(AST::Identifier {
-                        name: "b".into(),
-                        span: Span::new_dud()
-                    }),
-                    span: Span::new_dud()
-                }),
-            }
-        ))
+            }),
+        },
     )
 }
 
 #[allow(dead_code)]
-fn while_s(input: &str) -> ParseResult<AST> {
-    sliteral("while")
-        .and_right(expression)
-        .and_then(|conditional| {
-            statement.and_then(move |body| {
-                constant(AST::WhileLoop {
-                    span: Span::new_dud(),
-                    condition: Box::new(conditional.clone()),
-                    body: Box::new(body),
-                })
-            })
-        })
-        .parse(input)
+fn block_s(input: Source<'_>) -> ParseResult<'_, AST> {
+    located(|input| {
+        let (next, mut stmts, hint) = sliteral("{")
+            .and_right(zero_or_more(statement_or_error(false)))
+            .parse(input)?;
+        match sliteral("}").parse(next) {
+            Ok((next, _, close_hint)) => Ok((next, stmts, furthest(hint, close_hint))),
+            // a broken statement was skipped up to the end of the input. its
+            // error comes first, the missing `}` only after it
+            Err(err)
+                if stmts.iter().any(|stmt| matches!(stmt, AST::Error { .. }))
+                    && ignored.parse(next)?.0.rest().is_empty() =>
+            {
+                stmts.push(error_node(&err.merge_hint(hint)));
+                Ok((next, stmts, None))
+            }
+            Err(err) => Err(err.merge_hint(hint)),
+        }
+    })
+    .map(|(stmts, span)| AST::Block {
+        statements: stmts,
+        span,
+    })
+    .parse(input)
 }
 
 #[test]
-fn test_while_s() {
-    assert_eq!(
-        while_s("while a == b ret a;"),
-        Ok((
-            "",
-            AST::WhileLoop {
-                span: Span::new_dud(),
-                condition: Box::new(AST::Equals {
-                    lhs: Box::new(AST::Identifier {
-                        name: "a".into(),
-                        span: Span::new_dud()
-                    }),
-                    rhs: Box::new(AST::Identifier {
-                        name: "b".into(),
-                        span: Span::new_dud()
-                    })
-                }),
-                body: Box::new(AST::Return {
+fn test_block_s() {
+    assert_parses(
+        block_s,
+        "{ 1; ret a; 2 + 1; }",
+        "",
+        AST::Block {
+            statements: vec![
+                AST::Number {
+                    num: 1,
+                    span: Span::new_dud(),
+                },
+                AST::Return {
                     value: Box::new(AST::Identifier {
                         name: "a".into(),
-                        span: Span::new_dud()
+                        span: Span::new_dud(),
                     }),
-                    span: Span::new_dud()
-                }),
-            }
-        ))
+                    span: Span::new_dud(),
+                },
+                AST::Add {
+                    lhs: Box::new(AST::Number {
+                        num: 2,
+                        span: Span::new_dud(),
+                    }),
+                    rhs: Box::new(AST::Number {
+                        num: 1,
+                        span: Span::new_dud(),
+                    }),
+                },
+            ],
+            span: Span::new_dud(),
+        },
     )
 }
 
 #[allow(dead_code)]
-fn var_s(input: &str) -> ParseResult<AST> {
-    sliteral("mut")
-        .and_right(sidentifier)
-        .and_then(|var_name| {
-            sliteral("=").and_right(expression).and_then(move |value| {
-                sliteral("[;]").and_right(constant(AST::Variable {
-                    span: Span::new_dud(),
-                    name: var_name.clone(),
-                    value: Box::new(value),
-                }))
-            })
+fn params(input: Source<'_>) -> ParseResult<'_, Vec<String>> {
+    sidentifier
+        .and_then(|first| {
+            zero_or_more(sliteral(",").and_right(sidentifier))
+                .and_then(move |params| constant([vec![first.clone()], params].concat()))
         })
+        .or(constant(vec![]))
         .parse(input)
 }
 
-#[test]
-fn test_var_s() {
-    assert_eq!(
-        var_s("mut a = c;"),
-        Ok((
-            "",
-            AST::Variable {
-                span: Span::new_dud(),
-                name: "a".into(),
-                value: Box::new(AST::Identifier {
-                    name: "c".into(),
-                    span: Span::new_dud()
-                })
-            }
-        ))
-    );
-}
-
+// `a: int, b`, parameters with their annotations
 #[allow(dead_code)]
-fn assign_s(input: &str) -> ParseResult<AST> {
+fn typed_params(input: Source<'_>) -> ParseResult<'_, Vec<(String, Option<Type>)>> {
     sidentifier
-        .and_then(|var_name| {
-            sliteral("=").and_right(expression).and_then(move |val| {
-                sliteral("[;]").and_right(constant(AST::Assignment {
-                    span: Span::new_dud(),
-                    name: var_name.clone(),
-                    value: Box::new(val),
-                }))
-            })
+        .and_tuple(annotation)
+        .and_then(|first| {
+            zero_or_more(sliteral(",").and_right(sidentifier.and_tuple(annotation)))
+                .and_then(move |params| constant([vec![first.clone()], params].concat()))
         })
+        .or(constant(vec![]))
         .parse(input)
 }
 
-#[test]
-fn test_assign_s() {
-    assert_eq!(
-        assign_s("a = 300;"),
-        Ok((
-            "",
-            AST::Assignment {
-                span: Span::new_dud(),
-                name: "a".into(),
-                value: Box::new(AST::Number {
-                    num: 300,
-                    span: Span::new_dud()
-                })
-            }
-        ))
-    )
+// `-> type` after the parameters, which can be left out
+#[allow(dead_code)]
+fn ret_type(input: Source<'_>) -> ParseResult<'_, Option<Type>> {
+    maybe(sliteral("->").and_right(ty).map(Some), None)
+        .map(|mut found| found.pop().flatten())
+        .parse(input)
 }
 
 #[allow(dead_code)]
-fn block_s(input: &str) -> ParseResult<AST> {
-    sliteral("[{]")
-        .and_right(zero_or_more(statement))
-        .and_then(|stmts| {
-            sliteral("[}]").and_right(constant(AST::Block {
-                statements: stmts,
-                span: Span::new_dud(),
+fn fn_s(input: Source<'_>) -> ParseResult<'_, AST> {
+    located(sliteral(":").and_right(sidentifier).and_then(|fn_name| {
+        typed_params
+            .and_tuple(ret_type)
+            .and_then(closure!(clone fn_name, |signature| {
+                block_s.and_then(closure!(clone fn_name, clone signature, |blk| {
+                    constant((fn_name.clone(), signature.clone(), blk))
+                }))
             }))
-        })
-        .parse(input)
+    }))
+    .map(|((fn_name, (params, ret), blk), span)| {
+        let (params, types) = params.into_iter().unzip();
+        AST::FunctionDef {
+            span,
+            name: fn_name,
+            params,
+            types,
+            ret,
+            body: Box::new(blk),
+        }
+    })
+    .parse(input)
 }
 
 #[test]
-fn test_block_s() {
-    assert_eq!(
-        block_s("{ 1; ret a; 2 + 1; }"),
-        Ok((
-            "",
-            AST::Block {
-                statements: vec![
-                    AST::Number {
-                        num: 1,
-                        span: Span::new_dud()
-                    },
-                    AST::Return {
-                        value: Box::new(AST::Identifier {
+fn test_fn_s() {
+    assert_parses(
+        fn_s,
+        r":fib a: int, b -> int { ret a + b; }",
+        "",
+        AST::FunctionDef {
+            span: Span::new_dud(),
+            name: "fib".into(),
+            params: vec!["a".into(), "b".into()],
+            types: vec![Some(Type::Int), None],
+            ret: Some(Type::Int),
+            body: Box::new(AST::Block {
+                statements: vec![AST::Return {
+                    value: Box::new(AST::Add {
+                        lhs: Box::new(AST::Identifier {
                             name: "a".into(),
-                            span: Span::new_dud()
+                            span: Span::new_dud(),
                         }),
-                        span: Span::new_dud()
-                    },
-                    AST::Add {
-                        lhs: Box::new(AST::Number {
-                            num: 2,
-                            span: Span::new_dud()
+                        rhs: Box::new(AST::Identifier {
+                            name: "b".into(),
+                            span: Span::new_dud(),
                         }),
-                        rhs: Box::new(AST::Number {
-                            num: 1,
-                            span: Span::new_dud()
-                        })
-                    }
-                ],
-                span: Span::new_dud()
-            }
-        ))
-    )
+                    }),
+                    span: Span::new_dud(),
+                }],
+                span: Span::new_dud(),
+            }),
+        },
+    );
 }
 
 #[allow(dead_code)]
-fn params(input: &str) -> ParseResult<Vec<String>> {
-    sidentifier
-        .and_then(|first| {
-            zero_or_more(sliteral("[,]").and_right(sidentifier))
-                .and_then(move |params| constant(vec![vec![first.clone()], params].concat()))
-        })
-        .or(constant(vec![]))
+fn token(input: Source<'_>) -> ParseResult<'_, String> {
+    match_regex(r#""[^"]*"|'.'|[0-9]+|[A-Za-z_][A-Za-z0-9_]*|==|!=|<=|>=|&&|\|\||<<|>>|\.\.|\S"#)
         .parse(input)
 }
 
-#[allow(dead_code)]
-fn fn_s(input: &str) -> ParseResult<AST> {
-    sliteral(r":")
-        .and_right(sidentifier)
-        .and_then(|fn_name| {
-            params.and_then(closure!(clone fn_name, |parameters| {
-                block_s.and_then(closure!(clone fn_name, clone parameters, |blk| {
-                        constant(AST::FunctionDef {
-                            span: Span::new_dud(),
-                            name: fn_name.clone(),
-                            params: parameters.clone(),
-                            body: Box::new(blk),
-                        })
-                }))
-            }))
-        })
+pub fn sac_tokens(input: Source<'_>) -> ParseResult<'_, Vec<String>> {
+    zero_or_more(ignored.and_right(token))
+        .and_left(ignored)
         .parse(input)
 }
 
 #[test]
-fn test_fn_s() {
+fn test_sac_tokens() {
     assert_eq!(
-        fn_s(r":fib a, b { ret a + b; }"),
+        parse_str(
+            &sac_tokens,
+            ":main { printf(\"%d\\n\", a != 'b'); } // done"
+        ),
         Ok((
             "",
-            AST::FunctionDef {
-                span: Span::new_dud(),
-                name: "fib".into(),
-                params: vec!["a".into(), "b".into(),],
-                body: Box::new(AST::Block {
-                    statements: vec![AST::Return {
-                        value: Box::new(AST::Add {
-                            lhs: Box::new(AST::Identifier {
-                                name: "a".into(),
-                                span: Span::new_dud()
-                            }),
-                            rhs: Box::new(AST::Identifier {
-                                name: "b".into(),
-                                span: Span::new_dud()
-                            })
-                        }),
-                        span: Span::new_dud()
-                    }],
-                    span: Span::new_dud()
-                })
-            }
+            vec![
+                ":",
+                "main",
+                "{",
+                "printf",
+                "(",
+                "\"%d\\n\"",
+                ",",
+                "a",
+                "!=",
+                "'b'",
+                ")",
+                ";",
+                "}",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect()
         ))
     );
 }
 
-pub fn sac_parser(input: &str) -> ParseResult<AST> {
-    ignored
-        .and_right(zero_or_more(statement))
-        .map(|stmts| AST::Block {
-            statements: stmts,
-            span: Span::new_dud(),
-        })
-        .parse(input)
+// parses a whole sac file. if a statement cannot be parsed, the error
+// holds the input from where parsing stopped instead of silently
+// dropping the rest of the file
+pub fn sac_parser(input: Source<'_>) -> ParseResult<'_, AST> {
+    spanned(
+        ignored
+            .and_right(zero_or_more(statement_or_error(true)))
+            .and_left(ignored)
+            .and_left(end_of_input),
+    )
+    .map(|(stmts, span)| AST::Block {
+        statements: stmts,
+        span,
+    })
+    .parse(input)
+}
+
+#[test]
+fn test_sac_parser_requires_end_of_input() {
+    // whatever cannot be parsed is skipped and left as an error in the tree
+    for src in [
+        ":main { ret 0; }\n}  // stray",
+        ":main { ret 0; }\n:broken { mut x = ; }",
+    ] {
+        let (rest, ast) = parse_str(&sac_parser, src).unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(ast.errors().len(), 1);
+    }
+    assert!(parse_str(&sac_parser, ":main { ret 0; }\n// trailing comment\n").is_ok());
 }
 
 #[test]
@@ -1033,84 +2457,176 @@ fn test_sac_parser() {
     }
     ret res;
 }"#;
-    assert_eq!(
-        sac_parser(src),
-        Ok((
-            "",
-            AST::Block {
-                statements: vec![AST::FunctionDef {
-                    span: Span::new_dud(),
-                    name: "factorial".into(),
-                    params: vec!["n".into()],
-                    body: Box::new(AST::Block {
-                        statements: vec![
-                            AST::Variable {
-                                span: Span::new_dud(),
-                                name: "res".into(),
-                                value: Box::new(AST::Number {
-                                    num: 1,
-                                    span: Span::new_dud()
-                                })
-                            },
-                            AST::WhileLoop {
+    assert_parses(
+        sac_parser,
+        src,
+        "",
+        AST::Block {
+            statements: vec![AST::FunctionDef {
+                span: Span::new_dud(),
+                name: "factorial".into(),
+                params: vec!["n".into()],
+                types: vec![None],
+                ret: None,
+                body: Box::new(AST::Block {
+                    statements: vec![
+                        AST::Variable {
+                            span: Span::new_dud(),
+                            name: "res".into(),
+                            ty: None,
+                            value: Box::new(AST::Number {
+                                num: 1,
                                 span: Span::new_dud(),
-                                condition: Box::new(AST::NEquals {
-                                    lhs: Box::new(AST::Identifier {
-                                        name: "n".into(),
-                                        span: Span::new_dud()
-                                    }),
-                                    rhs: Box::new(AST::Number {
-                                        span: Span::new_dud(),
-                                        num: 1
-                                    }),
+                            }),
+                        },
+                        AST::WhileLoop {
+                            span: Span::new_dud(),
+                            step: None,
+                            condition: Box::new(AST::NEquals {
+                                lhs: Box::new(AST::Identifier {
+                                    name: "n".into(),
+                                    span: Span::new_dud(),
                                 }),
-                                body: Box::new(AST::Block {
-                                    statements: vec![
-                                        AST::Assignment {
-                                            span: Span::new_dud(),
-                                            name: "res".into(),
-                                            value: Box::new(AST::Multiply {
-                                                lhs: Box::new(AST::Identifier {
-                                                    name: "res".into(),
-                                                    span: Span::new_dud()
-                                                }),
-                                                rhs: Box::new(AST::Identifier {
-                                                    name: "n".into(),
-                                                    span: Span::new_dud()
-                                                }),
-                                            },)
-                                        },
-                                        AST::Assignment {
-                                            span: Span::new_dud(),
-                                            name: "n".into(),
-                                            value: Box::new(AST::Subtract {
-                                                lhs: Box::new(AST::Identifier {
-                                                    name: "n".into(),
-                                                    span: Span::new_dud()
-                                                }),
-                                                rhs: Box::new(AST::Number {
-                                                    span: Span::new_dud(),
-                                                    num: 1
-                                                }),
-                                            },)
-                                        }
-                                    ],
-                                    span: Span::new_dud()
-                                })
-                            },
-                            AST::Return {
-                                value: Box::new(AST::Identifier {
-                                    name: "res".into(),
-                                    span: Span::new_dud()
+                                rhs: Box::new(AST::Number {
+                                    span: Span::new_dud(),
+                                    num: 1,
                                 }),
-                                span: Span::new_dud()
-                            }
-                        ],
-                        span: Span::new_dud()
-                    })
-                }],
-                span: Span::new_dud()
-            }
-        ))
+                            }),
+                            body: Box::new(AST::Block {
+                                statements: vec![
+                                    AST::Assignment {
+                                        span: Span::new_dud(),
+                                        name: "res".into(),
+                                        value: Box::new(AST::Multiply {
+                                            lhs: Box::new(AST::Identifier {
+                                                name: "res".into(),
+                                                span: Span::new_dud(),
+                                            }),
+                                            rhs: Box::new(AST::Identifier {
+                                                name: "n".into(),
+                                                span: Span::new_dud(),
+                                            }),
+                                        }),
+                                    },
+                                    AST::Assignment {
+                                        span: Span::new_dud(),
+                                        name: "n".into(),
+                                        value: Box::new(AST::Subtract {
+                                            lhs: Box::new(AST::Identifier {
+                                                name: "n".into(),
+                                                span: Span::new_dud(),
+                                            }),
+                                            rhs: Box::new(AST::Number {
+                                                span: Span::new_dud(),
+                                                num: 1,
+                                            }),
+                                        }),
+                                    },
+                                ],
+                                span: Span::new_dud(),
+                            }),
+                        },
+                        AST::Return {
+                            value: Box::new(AST::Identifier {
+                                name: "res".into(),
+                                span: Span::new_dud(),
+                            }),
+                            span: Span::new_dud(),
+                        },
+                    ],
+                    span: Span::new_dud(),
+                }),
+            }],
+            span: Span::new_dud(),
+        },
     )
 }
+
+// where the first error in `src` is and what it says
+#[cfg(test)]
+fn parse_error_at(src: &str) -> (i32, i32, String) {
+    let (_, ast, _) = sac_parser(Source::from(src)).unwrap();
+    let errors = ast.errors();
+    let first = errors.first().expect("no parse errors");
+    (
+        first.span.start_line,
+        first.span.start_col,
+        first.msg.clone(),
+    )
+}
+
+#[test]
+fn test_sac_parser_errors() {
+    assert_eq!(
+        parse_error_at(":main {\n  mut x = ;\n}"),
+        (2, 11, "expected expression after `=`, found `;`".into())
+    );
+    assert_eq!(
+        parse_error_at(":main {\n  ret 1 + ;\n}"),
+        (2, 11, "expected expression, found `;`".into())
+    );
+    assert_eq!(
+        parse_error_at(":main {\n  x = 1\n}"),
+        (3, 1, "expected operator or `;`, found `}`".into())
+    );
+    assert_eq!(
+        parse_error_at(":main {\n  printf(\"%d\", 1;\n}"),
+        (2, 17, "expected operator, `,` or `)`, found `;`".into())
+    );
+    assert_eq!(
+        parse_error_at(":main { ret 0; }\n}"),
+        (2, 1, "expected statement, found `}`".into())
+    );
+}
+
+#[test]
+fn test_sac_parser_recovers() {
+    let src = ":main {\n  mut x = ;\n  ret 1 + ;\n  while x { y = }\n  ret 0;\n}\n:f a { ret a; }";
+    let (_, ast, _) = sac_parser(Source::from(src)).unwrap();
+    let errors: Vec<_> = ast
+        .errors()
+        .into_iter()
+        .map(|diag| (diag.span.start_line, diag.span.start_col))
+        .collect();
+    assert_eq!(errors, vec![(2, 11), (3, 11), (4, 17)]);
+
+    // the statements around the mistakes still make it into the tree
+    let AST::Block { statements, .. } = ast else {
+        panic!("expected a block")
+    };
+    assert_eq!(statements.len(), 2);
+    let AST::FunctionDef { body, .. } = &statements[0] else {
+        panic!("expected a function")
+    };
+    assert_eq!(body.children().len(), 4);
+}
+
+#[test]
+fn test_sac_parser_spans() {
+    let src = ":main {\n  mut greeting = \"hi\";\n  ret greeting + 1;\n}\n";
+    let (_, ast, _) = sac_parser(Source::new("spans.sac", src)).unwrap();
+    let pos = |span: Span| (span.start_line, span.start_col, span.end_line, span.end_col);
+
+    let AST::Block { statements, span } = ast else {
+        panic!("expected a block")
+    };
+    assert_eq!(span.file, "spans.sac");
+    let AST::FunctionDef { span, body, .. } = &statements[0] else {
+        panic!("expected a function")
+    };
+    assert_eq!(pos(span.clone()), (1, 1, 4, 2));
+    let AST::Block { statements, span } = body.as_ref() else {
+        panic!("expected a block")
+    };
+    assert_eq!(pos(span.clone()), (1, 7, 4, 2));
+    let AST::Variable { span, value, .. } = &statements[0] else {
+        panic!("expected a variable")
+    };
+    assert_eq!(pos(span.clone()), (2, 3, 2, 23));
+    assert_eq!(pos(value.get_span()), (2, 18, 2, 22));
+    let AST::Return { span, value } = &statements[1] else {
+        panic!("expected a return")
+    };
+    assert_eq!(pos(span.clone()), (3, 3, 3, 20));
+    assert_eq!(pos(value.get_span()), (3, 7, 3, 19));
+}
@@ -1,62 +1,78 @@
-use regex::Regex;
+use super::span::Span;
 
-#[derive(Debug, Clone)]
-pub enum ParseResult<T: Clone> {
-    Some(T, Source),
-    None,
-}
-
-#[allow(dead_code)]
-#[derive(Clone, Debug)]
-pub struct Source {
-    pub path: String,
-    pub content: String,
+// a position in a sac file. parsers take a Source and hand back the
+// Source just past whatever they consumed, so every result knows exactly
+// where it came from (byte offset, line and column, all starting at 1 except
+// the byte offset)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Source<'a> {
+    pub path: &'a str,
+    pub content: &'a str,
     pub index: usize,
-    pub lines: Vec<String>,
     pub line_no: usize,
     pub col_no: usize,
 }
 
-impl Source {
-    pub fn dud() -> Source {
+impl<'a> Source<'a> {
+    pub fn new(path: &'a str, content: &'a str) -> Source<'a> {
         Source {
-            path: "dud.txt".into(),
-            content: "".into(),
+            path,
+            content,
             index: 0,
-            lines: vec![],
-            line_no: 0,
-            col_no: 0,
+            line_no: 1,
+            col_no: 1,
         }
     }
 
-    pub fn from(content: String) -> Source {
-        Source {
-            path: "dud.txt".into(),
-            content,
-            index: 0,
-            lines: vec![],
-            line_no: 0,
-            col_no: 0,
-        }
+    // the input that has not been consumed yet
+    pub fn rest(&self) -> &'a str {
+        &self.content[self.index..]
     }
 
-    pub fn match_reg(&mut self, exp: Regex) -> ParseResult<String> {
-        let mat = exp.find_at(&self.content, self.index);
-        match mat {
-            Some(val) => {
-                if val.start() != self.index {
-                    ParseResult::None
-                } else {
-                    self.index += val.end() - val.start();
-                    let matched = self
-                        .content
-                        .get(val.start()..val.end())
-                        .unwrap()
-                        .to_string();
-                    ParseResult::Some(matched, self.clone())
-                }
+    // moves `len` bytes forward, keeping line and column in sync
+    pub fn advance(&self, len: usize) -> Source<'a> {
+        let mut next = *self;
+        for c in self.rest()[..len].chars() {
+            if c == '\n' {
+                next.line_no += 1;
+                next.col_no = 1;
+            } else {
+                next.col_no += 1;
             }
-            None => ParseResult::None,
         }
+        next.index += len;
+        next
+    }
+
+    // the span covering everything between self and `end`
+    pub fn span_to(&self, end: &Source) -> Span {
+        Span {
+            file: self.path.into(),
+            start_line: self.line_no as i32,
+            start_col: self.col_no as i32,
+            end_line: end.line_no as i32,
+            end_col: end.col_no as i32,
+        }
+    }
+}
+
+impl<'a> From<&'a str> for Source<'a> {
+    fn from(content: &'a str) -> Self {
+        Source::new("", content)
     }
 }
+
+#[test]
+fn test_source_advance() {
+    let src = Source::new("test.sac", "ab\ncd\n\nef");
+    let next = src.advance(4);
+    assert_eq!(next.rest(), "d\n\nef");
+    assert_eq!((next.line_no, next.col_no), (2, 2));
+    let next = next.advance(3);
+    assert_eq!((next.line_no, next.col_no), (4, 1));
+
+    let span = src.advance(1).span_to(&next);
+    assert_eq!(span.file, "test.sac");
+    assert_eq!((span.start_line, span.start_col), (1, 2));
+    assert_eq!((span.end_line, span.end_col), (4, 1));
+}
@@ -1,5 +1,7 @@
-use std::cmp;
+use std::{cmp, fmt};
 
+// lines and columns start at 1. the end is exclusive: it points just past
+// the last character covered by the span
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Span {
     pub file: String,
@@ -22,10 +24,14 @@ impl Span {
     }
 
     pub fn merge_with(&self, other: &Span) -> Span {
-        let start_line = cmp::min(self.start_line, other.start_line);
-        let start_col = cmp::min(self.start_col, other.start_col);
-        let end_line = cmp::max(self.end_line, other.end_line);
-        let end_col = cmp::max(self.end_col, other.end_col);
+        let (start_line, start_col) = cmp::min(
+            (self.start_line, self.start_col),
+            (other.start_line, other.start_col),
+        );
+        let (end_line, end_col) = cmp::max(
+            (self.end_line, self.end_col),
+            (other.end_line, other.end_col),
+        );
 
         Span {
             file: self.file.clone(),
@@ -36,3 +42,32 @@ impl Span {
         }
     }
 }
+
+// where the span starts, as `file:line:col`
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.start_line, self.start_col)
+    }
+}
+
+#[test]
+fn test_merge_with() {
+    let first = Span {
+        file: "a.sac".into(),
+        start_line: 1,
+        start_col: 9,
+        end_line: 1,
+        end_col: 12,
+    };
+    let second = Span {
+        file: "a.sac".into(),
+        start_line: 3,
+        start_col: 2,
+        end_line: 3,
+        end_col: 5,
+    };
+    let merged = first.merge_with(&second);
+    assert_eq!((merged.start_line, merged.start_col), (1, 9));
+    assert_eq!((merged.end_line, merged.end_col), (3, 5));
+    assert_eq!(second.merge_with(&first), merged);
+}
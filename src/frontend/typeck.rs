@@ -0,0 +1,785 @@
+use std::{collections::HashMap, mem};
+
+use super::ast::{NodeId, AST};
+use super::diagnostics::Diagnostic;
+use super::span::Span;
+use super::types::{Type, MAX_SIZE};
+
+struct Signature {
+    params: Vec<Type>,
+    ret: Type,
+}
+
+// gives every expression a type and checks that each use of it fits:
+// operands of operators, conditions, arguments, returned values and the
+// values stored in variables. unannotated locals take the type of their
+// value, unannotated parameters and return types are `Any` and not checked.
+// it runs on files that passed the checker, so every name resolves
+struct TypeChecker {
+    functions: HashMap<String, Signature>,
+    globals: HashMap<String, Type>,
+    consts: HashMap<String, i32>,
+    structs: Vec<String>,
+    // the types of the locals in view
+    locals: HashMap<String, Type>,
+    // what `ret` has to return in the function being checked
+    ret: Type,
+    // the type of each expression, what each `mut` holds and what each `*`
+    // assignment writes. codegen picks its loads, stores and 64-bit
+    // operations from them
+    types: HashMap<NodeId, Type>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+fn run(ast: &AST) -> TypeChecker {
+    let mut checker = TypeChecker {
+        functions: HashMap::new(),
+        globals: HashMap::new(),
+        consts: HashMap::new(),
+        structs: vec![],
+        locals: HashMap::new(),
+        ret: Type::Any,
+        types: HashMap::new(),
+        diagnostics: vec![],
+    };
+    checker.collect(ast);
+    checker.check(ast);
+    checker
+}
+
+// type checks a whole file, returning every mismatch in it
+pub fn check(ast: &AST) -> Vec<Diagnostic> {
+    run(ast).diagnostics
+}
+
+// the types in a checked file, for codegen
+pub fn types(ast: &AST) -> HashMap<NodeId, Type> {
+    run(ast).types
+}
+
+// whether `name`, declared in `statements`, is the start or end of a range
+// loop: `for i in start..end` declares `i` and `i.end` before its while
+// loop, and no variable written by hand has a `.` in its name
+fn is_range_bound(statements: &[AST], name: &str) -> bool {
+    let end = format!("{name}.end");
+    name.contains('.')
+        || statements
+            .iter()
+            .any(|stmt| matches!(stmt, AST::Variable { name, .. } if *name == end))
+}
+
+fn mismatch(span: Span, expected: &Type, found: &Type) -> Diagnostic {
+    Diagnostic::error(
+        span,
+        format!("mismatched types: expected `{expected}`, found `{found}`"),
+    )
+}
+
+impl TypeChecker {
+    // signatures, globals and structs, which can be used before the code
+    // declaring them
+    fn collect(&mut self, ast: &AST) {
+        for stmt in ast.children() {
+            match stmt {
+                AST::FunctionDef {
+                    name, types, ret, ..
+                } => {
+                    let params = types
+                        .iter()
+                        .map(|ty| ty.clone().unwrap_or(Type::Any))
+                        .collect();
+                    let ret = ret.clone().unwrap_or(Type::Any);
+                    self.functions
+                        .insert(name.clone(), Signature { params, ret });
+                }
+                AST::StructDef { name, .. } => self.structs.push(name.clone()),
+                _ => {}
+            }
+        }
+        for stmt in ast.children() {
+            if let AST::Global { name, value, .. } | AST::Const { name, value, .. } = stmt {
+                let ty = self.check(value);
+                if ty == Type::I64 {
+                    self.diagnostics.push(
+                        Diagnostic::error(value.get_span(), format!("`{name}` needs 64 bits"))
+                            .note("globals and constants are one word".into())
+                            .help("keep 64-bit values in locals".into()),
+                    );
+                }
+                if let (AST::Const { .. }, Some(value)) = (stmt, value.fold(&self.consts)) {
+                    self.consts.insert(name.clone(), value);
+                }
+                self.globals.insert(name.clone(), ty);
+            }
+        }
+    }
+
+    // a written type has to name a struct that exists
+    fn annotation(&mut self, ty: &Option<Type>, span: &Span) -> Type {
+        let Some(ty) = ty else {
+            return Type::Any;
+        };
+        let mut inner = ty;
+        while let Type::Pointer(to) | Type::Array(to, _) = inner {
+            inner = to;
+        }
+        if let Type::Struct(name) = inner {
+            if !self.structs.contains(name) {
+                self.diagnostics.push(
+                    Diagnostic::error(span.clone(), format!("cannot find type `{name}`"))
+                        .help(
+                            "the types are the integers, bool, char, string, pointers, arrays and structs"
+                                .into(),
+                        ),
+                );
+                return Type::Any;
+            }
+        }
+        self.sized(ty.clone(), span)
+    }
+
+    // an array has to fit in a frame
+    fn sized(&mut self, ty: Type, span: &Span) -> Type {
+        if ty.checked_size().is_some() {
+            return ty;
+        }
+        self.diagnostics.push(
+            Diagnostic::error(span.clone(), "array too large".into())
+                .note(format!("`{ty}` takes more than {MAX_SIZE} bytes")),
+        );
+        Type::Any
+    }
+
+    // the constants not shadowed by a local in view
+    fn consts(&self) -> HashMap<String, i32> {
+        let mut consts = self.consts.clone();
+        consts.retain(|name, _| !self.locals.contains_key(name));
+        consts
+    }
+
+    // `found` has to be usable as `expected`
+    fn expect(&mut self, expected: &Type, found: &Type, span: Span) {
+        if !expected.accepts(found) {
+            self.diagnostics.push(mismatch(span, expected, found));
+        }
+    }
+
+    // what is wrong with storing `value` where `expected` goes, if anything.
+    // integer literals take the type they are stored as, when they fit in it
+    fn fit(&mut self, expected: &Type, value: &AST) -> Option<Diagnostic> {
+        let found = self.check(value);
+        let literal = match value {
+            AST::Number { num, .. } => Some(*num),
+            _ if matches!(found, Type::Int | Type::Char) => {
+                value.fold(&self.consts()).map(i64::from)
+            }
+            _ => None,
+        };
+        if let (true, Some(literal)) = (expected.is_integer(), literal) {
+            if !expected.fits(literal) {
+                return Some(
+                    Diagnostic::error(
+                        value.get_span(),
+                        format!("literal out of range for `{expected}`"),
+                    )
+                    .note(format!("`{literal}` does not fit in `{expected}`"))
+                    .help(format!("use `as {expected}` to wrap it")),
+                );
+            }
+            // literals past the top of an int are i64s, until they are
+            // stored in a u32
+            if found == Type::I64 {
+                self.record(value, expected);
+            }
+            return None;
+        }
+        // narrower integers widen into an i64, as they do in arithmetic
+        if expected == &Type::I64 && found.is_numeric() {
+            return None;
+        }
+        // what is not annotated is a word, half an i64 would be lost
+        if expected == &Type::Any && found == Type::I64 {
+            return Some(
+                Diagnostic::error(value.get_span(), "an `i64` does not fit in a word".into())
+                    .note("unannotated parameters, return values and fields are 32 bits".into())
+                    .help("annotate it as `i64`, or use `as int`".into()),
+            );
+        }
+        match expected.accepts(&found) {
+            true => None,
+            false => Some(mismatch(value.get_span(), expected, &found)),
+        }
+    }
+
+    fn store(&mut self, expected: &Type, value: &AST) {
+        if let Some(diag) = self.fit(expected, value) {
+            self.diagnostics.push(diag);
+        }
+    }
+
+    fn record(&mut self, node: &AST, ty: &Type) {
+        self.types.insert(node.id(), ty.clone());
+    }
+
+    // an operand that has to be a number, and the type it has
+    fn numeric(&mut self, operand: &AST) -> Type {
+        let found = self.check(operand);
+        if !found.is_numeric() {
+            let diag = mismatch(operand.get_span(), &Type::Int, &found);
+            self.diagnostics.push(diag);
+        }
+        found
+    }
+
+    // a condition of an `if` or `while`
+    fn condition(&mut self, condition: &AST, word: &str) {
+        let found = self.check(condition);
+        if !Type::Bool.accepts(&found) {
+            let mut diag = mismatch(condition.get_span(), &Type::Bool, &found)
+                .note(format!("`{word}` conditions must be `bool`"));
+            if found.is_numeric() {
+                diag = diag.help("compare it with 0: `... != 0`".into());
+            }
+            self.diagnostics.push(diag);
+        }
+    }
+
+    // operands of arithmetic and bitwise operators are integers or chars.
+    // the result is an i64 when either of them is, else a u32 when either is
+    // one, else an int: every u8, u16 and char fits in an int, as in C. a
+    // shift is only unsigned when its lhs is
+    fn arithmetic(&mut self, ast: &AST, lhs: &AST, rhs: &AST) -> Type {
+        let (left, right) = (self.check(lhs), self.check(rhs));
+        if left.is_numeric() && right.is_numeric() {
+            let shift = matches!(ast, AST::ShiftLeft { .. } | AST::ShiftRight { .. });
+            return match (&left, &right) {
+                (Type::I64, _) | (_, Type::I64) => Type::I64,
+                (Type::U32, _) => Type::U32,
+                (_, Type::U32) if !shift => Type::U32,
+                _ => Type::Int,
+            };
+        }
+        let msg = match ast {
+            AST::Add { .. } => format!("cannot add `{right}` to `{left}`"),
+            AST::Subtract { .. } => format!("cannot subtract `{right}` from `{left}`"),
+            AST::Multiply { .. } => format!("cannot multiply `{left}` by `{right}`"),
+            AST::Divide { .. } => format!("cannot divide `{left}` by `{right}`"),
+            _ => format!("no arithmetic on `{left}` and `{right}`"),
+        };
+        self.diagnostics.push(
+            Diagnostic::error(ast.get_span(), msg)
+                .note("arithmetic is only defined on `int` and `char`".into()),
+        );
+        Type::Int
+    }
+
+    fn comparison(&mut self, ast: &AST, lhs: &AST, rhs: &AST, ordered: bool) -> Type {
+        let (left, right) = (self.check(lhs), self.check(rhs));
+        let comparable = match ordered {
+            true => left.is_numeric() && right.is_numeric(),
+            false => {
+                (left.is_numeric() && right.is_numeric())
+                    || left.accepts(&right)
+                    || right.accepts(&left)
+            }
+        };
+        if !comparable {
+            self.diagnostics.push(Diagnostic::error(
+                ast.get_span(),
+                format!("cannot compare `{left}` with `{right}`"),
+            ));
+        }
+        Type::Bool
+    }
+
+    fn logical(&mut self, lhs: &AST, rhs: &AST) -> Type {
+        for operand in [lhs, rhs] {
+            let found = self.check(operand);
+            self.expect(&Type::Bool, &found, operand.get_span());
+        }
+        Type::Bool
+    }
+
+    // declares the variable `bound` of a range loop holding `value`. one
+    // that is not an integer is reported once, as an int the loop itself
+    // checks fine
+    fn range_bound(&mut self, bound: &AST, name: &str, value: &AST) {
+        let mut ty = self.check(value);
+        if !ty.is_integer() && ty != Type::Any {
+            self.diagnostics.push(
+                Diagnostic::error(value.get_span(), "range bounds must be integers".into())
+                    .note(format!("found `{ty}`")),
+            );
+            ty = Type::Int;
+        }
+        self.record(bound, &ty);
+        self.locals.insert(name.into(), ty);
+    }
+
+    // what `*target` is
+    fn deref(&mut self, target: &AST) -> Type {
+        match self.check(target) {
+            Type::Pointer(to) => *to,
+            Type::Any => Type::Any,
+            found => {
+                self.diagnostics.push(Diagnostic::error(
+                    target.get_span(),
+                    format!("cannot dereference a value of type `{found}`"),
+                ));
+                Type::Any
+            }
+        }
+    }
+
+    fn element(&mut self, name: &str, index: &AST) -> Type {
+        self.numeric(index);
+        match self.locals.get(name) {
+            Some(Type::Array(of, _)) => *of.clone(),
+            _ => Type::Any,
+        }
+    }
+
+    fn variable(&self, name: &str) -> Type {
+        self.locals
+            .get(name)
+            .or_else(|| self.globals.get(name))
+            .cloned()
+            .unwrap_or(Type::Any)
+    }
+
+    // the type of `ast`, which is recorded. statements are `Any`
+    fn check(&mut self, ast: &AST) -> Type {
+        let ty = self.infer(ast);
+        if ty != Type::Any {
+            self.record(ast, &ty);
+        }
+        ty
+    }
+
+    fn infer(&mut self, ast: &AST) -> Type {
+        match ast {
+            AST::Number { num, .. } => match i32::try_from(*num) {
+                Ok(_) => Type::Int,
+                Err(_) => Type::I64,
+            },
+            AST::Bool { .. } => Type::Bool,
+            AST::Char { .. } => Type::Char,
+            AST::StrLiteral { .. } => Type::Str,
+            AST::Identifier { name, .. } => self.variable(name),
+            AST::Not { target, .. } => {
+                let found = self.check(target);
+                self.expect(&Type::Bool, &found, target.get_span());
+                Type::Bool
+            }
+            AST::Negate { target, .. } | AST::BitNot { target, .. } => match self.numeric(target) {
+                ty @ (Type::I64 | Type::U32) => ty,
+                _ => Type::Int,
+            },
+            AST::AddressOf { target, .. } => match self.check(target) {
+                // an array's address is that of its element 0
+                Type::Array(of, _) => Type::Pointer(of),
+                found => Type::Pointer(Box::new(found)),
+            },
+            AST::Deref { target, .. } => self.deref(target),
+            AST::Cast { span, value, ty } => {
+                let found = self.check(value);
+                let ty = self.annotation(&Some(ty.clone()), span);
+                if !ty.can_cast(&found) {
+                    self.diagnostics.push(
+                        Diagnostic::error(span.clone(), format!("cannot cast `{found}` to `{ty}`"))
+                            .note(
+                                "numbers cast to numbers, and strings and pointers to each other"
+                                    .into(),
+                            ),
+                    );
+                }
+                ty
+            }
+            AST::Equals { lhs, rhs } | AST::NEquals { lhs, rhs } => {
+                self.comparison(ast, lhs, rhs, false)
+            }
+            AST::LessThan { lhs, rhs }
+            | AST::GreaterThan { lhs, rhs }
+            | AST::LessEquals { lhs, rhs }
+            | AST::GreaterEquals { lhs, rhs } => self.comparison(ast, lhs, rhs, true),
+            AST::And { lhs, rhs } | AST::Or { lhs, rhs } => self.logical(lhs, rhs),
+            AST::Add { lhs, rhs }
+            | AST::Subtract { lhs, rhs }
+            | AST::Multiply { lhs, rhs }
+            | AST::Divide { lhs, rhs }
+            | AST::Modulo { lhs, rhs }
+            | AST::BitAnd { lhs, rhs }
+            | AST::BitOr { lhs, rhs }
+            | AST::BitXor { lhs, rhs }
+            | AST::ShiftLeft { lhs, rhs }
+            | AST::ShiftRight { lhs, rhs } => self.arithmetic(ast, lhs, rhs),
+            AST::Call { called, args, .. } => {
+                // C functions take and return anything, i64s included
+                let (params, ret) = match self.functions.get(called) {
+                    Some(signature) => (signature.params.clone(), signature.ret.clone()),
+                    None => (vec![], Type::Any),
+                };
+                for (i, arg) in args.iter().enumerate() {
+                    match params.get(i) {
+                        Some(param) => self.store(param, arg),
+                        None => {
+                            self.check(arg);
+                        }
+                    }
+                }
+                ret
+            }
+            AST::Return { value, .. } => {
+                let ret = self.ret.clone();
+                if let Some(mut diag) = self.fit(&ret, value) {
+                    if ret != Type::Any {
+                        diag = diag.note(format!("the function returns `{ret}`"));
+                    }
+                    self.diagnostics.push(diag);
+                }
+                Type::Any
+            }
+            AST::Block { statements, .. } => {
+                let outer_locals = self.locals.clone();
+                for stmt in statements {
+                    match stmt {
+                        AST::Variable { name, value, .. } if is_range_bound(statements, name) => {
+                            self.range_bound(stmt, name, value)
+                        }
+                        _ => {
+                            self.check(stmt);
+                        }
+                    }
+                }
+                self.locals = outer_locals;
+                Type::Any
+            }
+            AST::IfCond {
+                condition,
+                then,
+                c_else,
+                ..
+            } => {
+                self.condition(condition, "if");
+                self.check(then);
+                if let Some(c_else) = c_else {
+                    self.check(c_else);
+                }
+                Type::Any
+            }
+            AST::WhileLoop {
+                condition,
+                body,
+                step,
+                ..
+            } => {
+                self.condition(condition, "while");
+                self.check(body);
+                if let Some(step) = step {
+                    self.check(step);
+                }
+                Type::Any
+            }
+            AST::FunctionDef {
+                span,
+                params,
+                types,
+                ret,
+                body,
+                ..
+            } => {
+                let mut locals = HashMap::new();
+                for (param, ty) in params.iter().zip(types) {
+                    let ty = self.annotation(ty, span);
+                    locals.insert(param.clone(), ty);
+                }
+                let ret = self.annotation(ret, span);
+                let outer_locals = mem::replace(&mut self.locals, locals);
+                let outer_ret = mem::replace(&mut self.ret, ret);
+                self.check(body);
+                self.locals = outer_locals;
+                self.ret = outer_ret;
+                Type::Any
+            }
+            AST::Variable {
+                span,
+                name,
+                ty,
+                value,
+            } => {
+                let expected = ty.as_ref().map(|_| self.annotation(ty, span));
+                let found = match (value.as_ref(), &expected) {
+                    (AST::Array { value, len, .. }, expected) => {
+                        let len = len.fold(&self.consts()).unwrap_or(1);
+                        match expected {
+                            // so `[0; 8]` can fill a `[u8; 8]`
+                            Some(Type::Array(of, n)) if *n == len => {
+                                self.store(of, value);
+                                Type::Array(of.clone(), len)
+                            }
+                            _ => Type::Array(Box::new(self.check(value)), len),
+                        }
+                    }
+                    (AST::StructLit { name, fields, .. }, _) => {
+                        for (_, value) in fields {
+                            self.store(&Type::Any, value);
+                        }
+                        Type::Struct(name.clone())
+                    }
+                    (value, Some(expected)) => {
+                        self.store(expected, value);
+                        expected.clone()
+                    }
+                    (value, None) => self.check(value),
+                };
+                let ty = match expected {
+                    Some(expected) => {
+                        self.expect(&expected, &found, value.get_span());
+                        expected
+                    }
+                    None => self.sized(found, span),
+                };
+                self.record(ast, &ty);
+                self.locals.insert(name.clone(), ty);
+                Type::Any
+            }
+            AST::Assignment { name, value, .. } => {
+                let expected = self.variable(name);
+                self.store(&expected, value);
+                Type::Any
+            }
+            AST::Index { name, index, .. } => self.element(name, index),
+            AST::IndexAssign {
+                name, index, value, ..
+            } => {
+                let expected = self.element(name, index);
+                self.store(&expected, value);
+                Type::Any
+            }
+            AST::DerefAssign { target, value, .. } => {
+                let expected = self.deref(target);
+                self.record(ast, &expected);
+                self.store(&expected, value);
+                Type::Any
+            }
+            // fields are not typed
+            AST::Field { .. } => Type::Any,
+            AST::FieldAssign { value, .. } => {
+                self.store(&Type::Any, value);
+                Type::Any
+            }
+            // collect has checked them
+            AST::Global { .. } | AST::Const { .. } => Type::Any,
+            _ => {
+                for child in ast.children() {
+                    self.check(child);
+                }
+                Type::Any
+            }
+        }
+    }
+}
+
+// the messages `check` produces for `src`, with the line each one is on
+#[cfg(test)]
+fn check_src(src: &str) -> Vec<(i32, String)> {
+    use super::{checker, sac_parser::sac_parser, source::Source};
+
+    let (_, ast, _) = sac_parser(Source::from(src)).unwrap();
+    assert!(ast.errors().is_empty());
+    assert!(checker::check(&ast).is_empty());
+    check(&ast)
+        .into_iter()
+        .map(|diag| (diag.span.start_line, diag.msg))
+        .collect()
+}
+
+#[test]
+fn test_typeck() {
+    let src = "\
+:main -> int {
+  mut n: int = 3;
+  mut s = \"text\";
+  mut ok = n > 2 && !false;
+  mut c: char = 'c';
+  n = c - 'a' + n;
+  ret n;
+}
+:shout s: string, times: int -> bool {
+  mut p: *int = &times;
+  *p = *p - 1;
+  ret times > 0;
+}";
+    assert_eq!(check_src(src), vec![]);
+}
+
+#[test]
+fn test_typeck_mismatches() {
+    let src = "\
+:main -> int {
+  mut n = 3;
+  mut s = \"text\";
+  n = s + 1;
+  if n { ret 1; }
+  while s == n { }
+  mut b: bool = 1;
+  mut p: *int = &s;
+  n = *n;
+  greet(1);
+  ret greet(s);
+}
+:greet s: string -> bool { ret s; }";
+    assert_eq!(
+        check_src(src),
+        vec![
+            (4, "cannot add `int` to `string`".into()),
+            (5, "mismatched types: expected `bool`, found `int`".into()),
+            (6, "cannot compare `string` with `int`".into()),
+            (7, "mismatched types: expected `bool`, found `int`".into()),
+            (
+                8,
+                "mismatched types: expected `*int`, found `*string`".into()
+            ),
+            (9, "cannot dereference a value of type `int`".into()),
+            (
+                10,
+                "mismatched types: expected `string`, found `int`".into()
+            ),
+            (11, "mismatched types: expected `int`, found `bool`".into()),
+            (
+                13,
+                "mismatched types: expected `bool`, found `string`".into()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_typeck_ranges() {
+    use super::{sac_parser::sac_parser, source::Source};
+
+    let src = "\
+:main {
+  mut n: u8 = 3;
+  for i in 0..n { }
+  for i in \"a\"..\"b\" { }
+  for c in 'a'..true { }
+}";
+    let (_, ast, _) = sac_parser(Source::from(src)).unwrap();
+    let found: Vec<_> = check(&ast)
+        .into_iter()
+        .map(|diag| (diag.span.start_line, diag.span.start_col, diag.msg))
+        .collect();
+    // one error for each bound that is not an integer, under that bound
+    let bad = "range bounds must be integers".to_string();
+    assert_eq!(
+        found,
+        vec![
+            (4, 12, bad.clone()),
+            (4, 17, bad.clone()),
+            (5, 12, bad.clone()),
+            (5, 17, bad)
+        ]
+    );
+}
+
+#[test]
+fn test_typeck_aggregates() {
+    let src = "\
+struct Point { x, y }
+const N = 4;
+global flag = N > 2;
+:main {
+  mut buf = [0; N];
+  buf[1] = \"c\";
+  buf[true] = 2;
+  mut p: Point = Point { x: 1, y: 2 };
+  mut q: *Point = &p;
+  mut r: *Line = &p;
+  mut first: *int = &buf;
+  if flag { ret buf[0]; }
+}";
+    assert_eq!(
+        check_src(src),
+        vec![
+            (6, "mismatched types: expected `int`, found `string`".into()),
+            (7, "mismatched types: expected `int`, found `bool`".into()),
+            (10, "cannot find type `Line`".into()),
+        ]
+    );
+
+    let src = "\
+:main {
+  mut fits: [i64; 2097152] = [0; 2097152];
+  mut big: [i64; 300000000] = [0; 300000000];
+  mut wraps = [0; 2147483647];
+}";
+    assert_eq!(
+        check_src(src),
+        vec![(3, "array too large".into()), (4, "array too large".into()),]
+    );
+}
+
+#[test]
+fn test_typeck_sized() {
+    let src = "\
+:main {
+  mut b: u8 = 255;
+  mut big: u8 = 256;
+  mut neg: i8 = -129;
+  mut sum: u8 = b + 1;
+  b = (b + 1) as u8;
+  mut wide: i16 = b as i16;
+  mut s = \"hi\" as *u8;
+  mut flag = true as u8;
+  mut n = \"hi\" as int;
+  mut buf: [u16; 4] = [65535; 4];
+  buf[b] = wide;
+  mut u: u32 = 4294967295;
+  mut over: u32 = 4294967296;
+  mut i: int = u / 2;
+  mut c: bool = u > 2147483648;
+}";
+    assert_eq!(
+        check_src(src),
+        vec![
+            (3, "literal out of range for `u8`".into()),
+            (4, "literal out of range for `i8`".into()),
+            (5, "mismatched types: expected `u8`, found `int`".into()),
+            (10, "cannot cast `string` to `int`".into()),
+            (12, "mismatched types: expected `u16`, found `i16`".into()),
+            (14, "literal out of range for `u32`".into()),
+            (15, "mismatched types: expected `int`, found `u32`".into()),
+        ]
+    );
+}
+
+#[test]
+fn test_typeck_i64() {
+    let src = "\
+global huge = 1 as i64;
+:twice n: i64 -> i64 { ret n * 2; }
+:id x { ret x; }
+:main {
+  mut big = 5000000000;
+  mut n: int = 3;
+  mut sum = big + n;
+  mut w: i64 = 7;
+  w = twice(n);
+  w = twice(n as i64) + sum;
+  n = big;
+  n = (big >> 32) as int;
+  n = id(big);
+  printf(\"%lld\\n\", big);
+  ret big;
+}";
+    assert_eq!(
+        check_src(src),
+        vec![
+            (1, "`huge` needs 64 bits".into()),
+            (11, "mismatched types: expected `int`, found `i64`".into()),
+            (13, "an `i64` does not fit in a word".into()),
+            (15, "an `i64` does not fit in a word".into()),
+        ]
+    );
+}
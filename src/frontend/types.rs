@@ -0,0 +1,173 @@
+use std::fmt;
+
+// the most bytes a value can take. the type checker reports bigger arrays,
+// which keeps the offsets into a frame far from overflowing
+pub const MAX_SIZE: i32 = 1 << 24;
+
+// the static type of a value. all but `i64`, which is held in r0:r1, fit in
+// a word, though the narrow integers take less space in memory
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Type {
+    // `int`, also written `i32`
+    Int,
+    I8,
+    I16,
+    U8,
+    U16,
+    U32,
+    I64,
+    Bool,
+    Char,
+    Str,
+    Pointer(Box<Type>),
+    Array(Box<Type>, i32),
+    Struct(String),
+    // anything: what C functions return, and unannotated parameters and
+    // return types. it is compatible with every other type
+    Any,
+}
+
+#[allow(dead_code)]
+impl Type {
+    // the type a written name stands for. names that are not built in are
+    // structs
+    pub fn named(name: &str) -> Type {
+        match name {
+            "int" | "i32" => Type::Int,
+            "i8" => Type::I8,
+            "i16" => Type::I16,
+            "u8" => Type::U8,
+            "u16" => Type::U16,
+            "u32" => Type::U32,
+            "i64" => Type::I64,
+            "bool" => Type::Bool,
+            "char" => Type::Char,
+            "string" => Type::Str,
+            _ => Type::Struct(name.into()),
+        }
+    }
+
+    // whether a value of type `other` can be used where `self` is expected
+    pub fn accepts(&self, other: &Type) -> bool {
+        match (self, other) {
+            (Type::Any, _) | (_, Type::Any) => true,
+            (Type::Pointer(to), Type::Pointer(other_to)) => to.accepts(other_to),
+            (Type::Array(of, len), Type::Array(other_of, other_len)) => {
+                len == other_len && of.accepts(other_of)
+            }
+            _ => self == other,
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            Type::Int | Type::I8 | Type::I16 | Type::U8 | Type::U16 | Type::U32 | Type::I64
+        )
+    }
+
+    // integers and chars take part in arithmetic, chars as their code
+    pub fn is_numeric(&self) -> bool {
+        self.is_integer() || matches!(self, Type::Char | Type::Any)
+    }
+
+    // whether a literal `value` can be stored in an integer of this type
+    pub fn fits(&self, value: i64) -> bool {
+        match self {
+            Type::Int => i32::try_from(value).is_ok(),
+            Type::I8 => i8::try_from(value).is_ok(),
+            Type::I16 => i16::try_from(value).is_ok(),
+            Type::U8 => u8::try_from(value).is_ok(),
+            Type::U16 => u16::try_from(value).is_ok(),
+            Type::U32 => u32::try_from(value).is_ok(),
+            _ => true,
+        }
+    }
+
+    // whether `value as self` is allowed: between numbers, from a bool to an
+    // integer, and between strings and pointers
+    pub fn can_cast(&self, value: &Type) -> bool {
+        match (value, self) {
+            (Type::Any, _) | (_, Type::Any) => true,
+            (Type::Bool, to) => to.is_integer() || to == &Type::Bool,
+            (Type::Str | Type::Pointer(_), Type::Str | Type::Pointer(_)) => true,
+            (from, to) => from.is_numeric() && to.is_numeric(),
+        }
+    }
+
+    // what `value` becomes once converted to this type
+    pub fn wrap(&self, value: i32) -> i32 {
+        match self {
+            Type::I8 => value as i8 as i32,
+            Type::I16 => value as i16 as i32,
+            Type::U8 => value as u8 as i32,
+            Type::U16 => value as u16 as i32,
+            _ => value,
+        }
+    }
+
+    // the bytes a value takes in memory, capped at `MAX_SIZE`
+    pub fn size(&self) -> i32 {
+        self.checked_size().unwrap_or(MAX_SIZE)
+    }
+
+    // the bytes a value takes in memory, or `None` past `MAX_SIZE`
+    pub fn checked_size(&self) -> Option<i32> {
+        match self {
+            Type::I8 | Type::U8 => Some(1),
+            Type::I16 | Type::U16 => Some(2),
+            Type::I64 => Some(8),
+            Type::Array(of, len) => of
+                .checked_size()?
+                .checked_mul(*len)
+                .filter(|size| *size <= MAX_SIZE),
+            _ => Some(4),
+        }
+    }
+
+    // the words of the frame a local of this type takes
+    pub fn words(&self) -> i32 {
+        (self.size() + 3) / 4
+    }
+
+    // the instructions that read and write a value of this type. narrow
+    // integers are sign or zero extended to a full word as they are read
+    pub fn load(&self) -> &'static str {
+        match self {
+            Type::I8 => "ldrsb",
+            Type::U8 => "ldrb",
+            Type::I16 => "ldrsh",
+            Type::U16 => "ldrh",
+            _ => "ldr",
+        }
+    }
+
+    pub fn store(&self) -> &'static str {
+        match self.size() {
+            1 => "strb",
+            2 => "strh",
+            _ => "str",
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::I8 => write!(f, "i8"),
+            Type::I16 => write!(f, "i16"),
+            Type::U8 => write!(f, "u8"),
+            Type::U16 => write!(f, "u16"),
+            Type::U32 => write!(f, "u32"),
+            Type::I64 => write!(f, "i64"),
+            Type::Bool => write!(f, "bool"),
+            Type::Char => write!(f, "char"),
+            Type::Str => write!(f, "string"),
+            Type::Pointer(to) => write!(f, "*{to}"),
+            Type::Array(of, len) => write!(f, "[{of}; {len}]"),
+            Type::Struct(name) => write!(f, "{name}"),
+            Type::Any => write!(f, "_"),
+        }
+    }
+}
@@ -20,7 +20,7 @@ fn quoted_string<'a>() -> impl Parser<'a, String> {
 fn test_quoted_string() {
     assert_eq!(
         Ok(("", "Hello, World!".into())),
-        quoted_string().parse("\"Hello, World!\"")
+        parse_str(&quoted_string(), "\"Hello, World!\"")
     )
 }
 
@@ -38,7 +38,7 @@ fn attributes<'a>() -> impl Parser<'a, Vec<(String, String)>> {
 fn test_attributes() {
     let parser = attributes();
     assert_eq!(
-        parser.parse(" name=\"Joshua\" age=\"23\""),
+        parse_str(&parser, " name=\"Joshua\" age=\"23\""),
         Ok((
             "",
             vec![
@@ -68,7 +68,7 @@ fn test_single_element() {
     let parser = single_element();
     let html = r#"<a id="go_to_google" href="https://www.google.com" onClick="doSomething()"/>"#;
     assert_eq!(
-        parser.parse(html),
+        parse_str(&parser, html),
         Ok((
             "",
             Element {
@@ -146,5 +146,5 @@ fn test_xml_parser() {
         ],
     };
 
-    assert_eq!(Ok(("", parsed_doc)), element().parse(doc));
+    assert_eq!(Ok(("", parsed_doc)), parse_str(&element(), doc));
 }
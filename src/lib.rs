@@ -0,0 +1,41 @@
+//! sac compiles `.sac` files to ARM32 assembly.
+//!
+//! The whole pipeline is one call:
+//!
+//! ```
+//! use sac::Source;
+//!
+//! let src = ":main {\n  printf(\"%d\\n\", 6 * 7);\n}\n";
+//! let asm = sac::compile(Source::new("answer.sac", src)).unwrap();
+//! assert!(asm.contains("main:"));
+//! ```
+//!
+//! It can also be run a step at a time, to look at the [`AST`] or to only
+//! check a file:
+//!
+//! ```
+//! use sac::Source;
+//!
+//! let src = ":main {\n  ret twice(1, 2);\n}\n:twice n { ret n * 2; }\n";
+//! let ast = sac::parse(Source::new("twice.sac", src)).unwrap();
+//! let errors = sac::check(&ast).unwrap_err();
+//! assert_eq!(
+//!     errors[0].to_string(),
+//!     "twice.sac:2:7: `twice` takes 1 argument but 2 were supplied"
+//! );
+//! // rustc-style, with the offending line underlined
+//! eprint!("{}", errors[0].render(src));
+//! ```
+//!
+//! None of these functions panic or exit on bad input; every problem comes
+//! back as a [`CompileError`].
+
+pub mod codegen;
+pub mod compiler;
+pub mod frontend;
+
+pub use compiler::{
+    check, compile, compile_to, compile_with, emit, emit_to, emit_with, parse, CompileError,
+    Settings,
+};
+pub use frontend::{ast::AST, diagnostics::Diagnostic, source::Source};
@@ -1,12 +1,78 @@
-pub mod codegen;
-pub mod frontend;
+mod cli;
 
-use codegen::builder::Builder;
-use frontend::sac_parser::sac_parser;
+use std::{env, fs, io, process};
+
+use cli::{Emit, Options, USAGE};
+use sac::{frontend::sac_parser::sac_tokens, CompileError, Settings, Source};
+
+fn io_error(path: &str, err: io::Error) -> String {
+    let err = CompileError::Io {
+        path: path.into(),
+        msg: err.to_string(),
+    };
+    err.render("")
+}
+
+fn render(errors: &[CompileError], src: &str) -> String {
+    errors.iter().map(|err| err.render(src)).collect()
+}
+
+// compiles one file. errors come back fully rendered, ready for stderr
+fn compile_file(input: &str, opts: &Options) -> Result<(), String> {
+    let src = fs::read_to_string(input).map_err(|err| io_error(input, err))?;
+    let output = opts.output_for(input);
+    let source = Source::new(input, &src);
+
+    let text = match opts.emit {
+        Emit::Tokens => {
+            let (_, tokens, _) =
+                sac_tokens(source).map_err(|_| format!("error: cannot tokenize `{input}`\n"))?;
+            tokens.join("\n") + "\n"
+        }
+        Emit::Ast => {
+            let ast = sac::parse(source).map_err(|errors| render(&errors, &src))?;
+            format!("{ast:#?}\n")
+        }
+        Emit::Asm => {
+            let settings = Settings {
+                bounds_checks: opts.bounds_checks,
+            };
+            sac::compile_with(source, settings).map_err(|errors| render(&errors, &src))?
+        }
+    };
+
+    match output {
+        Some(path) => fs::write(&path, text).map_err(|err| io_error(&path, err)),
+        None => {
+            print!("{text}");
+            Ok(())
+        }
+    }
+}
 
 fn main() {
-    let mut b = Builder::n("./play.sac".into());
-    let (_, res) = sac_parser(&b.get_src()).unwrap();
-    res.emit_arm32(&mut b);
-    b.write_out();
+    let opts = match Options::from_args(env::args().skip(1)) {
+        Ok(opts) => opts,
+        Err(msg) => {
+            eprintln!("error: {msg}\n\n{USAGE}");
+            process::exit(2);
+        }
+    };
+
+    if opts.help {
+        println!("{USAGE}");
+        return;
+    }
+
+    let mut failed = false;
+    for input in &opts.inputs {
+        if let Err(msg) = compile_file(input, &opts) {
+            eprint!("{msg}");
+            failed = true;
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
 }